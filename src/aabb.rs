@@ -25,6 +25,23 @@ impl Aabb {
 
         true
     }
+
+    // like `hit` but returns the part of [tmin, tmax] that is inside the box
+    pub fn hit_range(&self, r: &Ray, mut tmin: f32, mut tmax: f32) -> Option<(f32, f32)> {
+        for a in 0..3 {
+            let mint = (self.min[a] - r.origin[a]) / r.direction[a];
+            let maxt = (self.max[a] - r.origin[a]) / r.direction[a];
+
+            tmin = mint.min(maxt).max(tmin);
+            tmax = mint.max(maxt).min(tmax);
+
+            if tmax <= tmin {
+                return None;
+            }
+        }
+
+        Some((tmin, tmax))
+    }
 }
 
 pub fn surrounding_box(box_0: &Aabb, box_1: &Aabb) -> Aabb {
@@ -0,0 +1,82 @@
+use crate::color::{Color, BLACK};
+
+// A pixel keeps being sampled until the standard error of its mean luminance drops
+// below `threshold` times the mean, but always takes between `min_spp` and `max_spp`.
+#[derive(Copy, Clone, Debug)]
+pub struct AdaptiveSettings {
+    pub min_spp: u32,
+    pub max_spp: u32,
+    pub threshold: f32,
+    // also write where the samples went, see `Film::heatmap`
+    pub heatmap: bool,
+}
+
+impl AdaptiveSettings {
+    pub fn new(min_spp: u32, max_spp: u32, threshold: f32) -> AdaptiveSettings {
+        AdaptiveSettings {
+            min_spp: min_spp.max(2),
+            max_spp: max_spp.max(min_spp),
+            threshold,
+            heatmap: false,
+        }
+    }
+}
+
+// Welford's running mean and variance of one pixel's samples
+#[derive(Copy, Clone, Debug)]
+pub struct PixelStats {
+    pub n: u32,
+    pub mean: Color,
+    mean_luminance: f32,
+    m2: f32,
+}
+
+pub fn luminance(c: &Color) -> f32 {
+    0.2126 * c.r + 0.7152 * c.g + 0.0722 * c.b
+}
+
+impl Default for PixelStats {
+    fn default() -> Self {
+        PixelStats::new()
+    }
+}
+
+impl PixelStats {
+    pub fn new() -> PixelStats {
+        PixelStats {
+            n: 0,
+            mean: BLACK,
+            mean_luminance: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, sample: Color) {
+        self.n += 1;
+        let n = self.n as f32;
+        self.mean = self.mean + (sample - self.mean) / n;
+
+        let l = luminance(&sample);
+        let delta = l - self.mean_luminance;
+        self.mean_luminance += delta / n;
+        self.m2 += delta * (l - self.mean_luminance);
+    }
+
+    pub fn variance(&self) -> f32 {
+        if self.n < 2 {
+            return f32::INFINITY;
+        }
+        self.m2 / (self.n - 1) as f32
+    }
+
+    // standard error of the mean relative to the mean, dark pixels use an
+    // absolute floor so pure black does not demand infinite samples
+    pub fn relative_error(&self) -> f32 {
+        (self.variance() / self.n as f32).sqrt() / self.mean_luminance.max(1e-2)
+    }
+
+    pub fn converged(&self, adaptive: &AdaptiveSettings) -> bool {
+        self.n >= adaptive.max_spp
+            || (self.n >= adaptive.min_spp && self.relative_error() < adaptive.threshold)
+    }
+}
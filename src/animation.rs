@@ -0,0 +1,249 @@
+use image::ImageResult;
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::time::Instant;
+
+use crate::color::Color;
+use crate::projection::{Perspective, Projection};
+use crate::rendering::{Camera, DenoiseSettings, RenderSettings};
+use crate::vec3::Vec3;
+use crate::world::World;
+
+#[derive(Copy, Clone, Debug)]
+pub struct CameraKeyframe {
+    pub frame: f32,
+    pub lookfrom: Vec3,
+    pub lookat: Vec3,
+    pub vfov: f32,
+    pub focus_dist: f32,
+}
+
+impl CameraKeyframe {
+    pub fn new(
+        frame: f32,
+        lookfrom: Vec3,
+        lookat: Vec3,
+        vfov: f32,
+        focus_dist: f32,
+    ) -> CameraKeyframe {
+        CameraKeyframe {
+            frame,
+            lookfrom,
+            lookat,
+            vfov,
+            focus_dist,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum Interpolation {
+    Linear,
+    CatmullRom,
+}
+
+pub struct CameraPath {
+    pub keyframes: Vec<CameraKeyframe>,
+    pub interpolation: Interpolation,
+    pub vup: Vec3,
+    pub aspect_ratio: f32,
+    pub aperture: f32,
+    pub fps: f32,
+    // fraction of a frame the shutter stays open for
+    pub shutter: f32,
+    pub projection: Projection,
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * ((2.0 * p1)
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
+fn catmull_rom_vec(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> Vec3 {
+    Vec3::new(
+        catmull_rom(p0.x, p1.x, p2.x, p3.x, t),
+        catmull_rom(p0.y, p1.y, p2.y, p3.y, t),
+        catmull_rom(p0.z, p1.z, p2.z, p3.z, t),
+    )
+}
+
+impl CameraPath {
+    pub fn new(
+        mut keyframes: Vec<CameraKeyframe>,
+        interpolation: Interpolation,
+        vup: Vec3,
+        aspect_ratio: f32,
+        aperture: f32,
+    ) -> CameraPath {
+        assert!(!keyframes.is_empty(), "camera path needs a keyframe");
+        keyframes.sort_by(|a, b| a.frame.total_cmp(&b.frame));
+
+        CameraPath {
+            keyframes,
+            interpolation,
+            vup,
+            aspect_ratio,
+            aperture,
+            fps: 24.0,
+            shutter: 0.5,
+            projection: Perspective::new(),
+        }
+    }
+
+    pub fn sample(&self, frame: f32) -> CameraKeyframe {
+        let keys = &self.keyframes;
+        let last = keys.len() - 1;
+        if frame <= keys[0].frame {
+            return keys[0];
+        }
+        if frame >= keys[last].frame {
+            return keys[last];
+        }
+
+        let i2 = keys.iter().position(|k| k.frame > frame).unwrap();
+        let i1 = i2 - 1;
+        let (k1, k2) = (keys[i1], keys[i2]);
+        let t = (frame - k1.frame) / (k2.frame - k1.frame);
+
+        match self.interpolation {
+            Interpolation::Linear => CameraKeyframe {
+                frame,
+                lookfrom: k1.lookfrom + (k2.lookfrom - k1.lookfrom) * t,
+                lookat: k1.lookat + (k2.lookat - k1.lookat) * t,
+                vfov: lerp(k1.vfov, k2.vfov, t),
+                focus_dist: lerp(k1.focus_dist, k2.focus_dist, t),
+            },
+            Interpolation::CatmullRom => {
+                let k0 = keys[i1.saturating_sub(1)];
+                let k3 = keys[(i2 + 1).min(last)];
+                CameraKeyframe {
+                    frame,
+                    lookfrom: catmull_rom_vec(
+                        k0.lookfrom,
+                        k1.lookfrom,
+                        k2.lookfrom,
+                        k3.lookfrom,
+                        t,
+                    ),
+                    lookat: catmull_rom_vec(k0.lookat, k1.lookat, k2.lookat, k3.lookat, t),
+                    vfov: catmull_rom(k0.vfov, k1.vfov, k2.vfov, k3.vfov, t),
+                    focus_dist: catmull_rom(
+                        k0.focus_dist,
+                        k1.focus_dist,
+                        k2.focus_dist,
+                        k3.focus_dist,
+                        t,
+                    ),
+                }
+            }
+        }
+    }
+
+    // the shutter opens at frame / fps seconds, the same clock `Animated` keyframes use
+    pub fn camera(&self, frame: u32) -> Camera {
+        let key = self.sample(frame as f32);
+        let time_0 = frame as f32 / self.fps;
+        let time_1 = time_0 + self.shutter / self.fps;
+
+        let mut camera = Camera::new(
+            key.lookfrom,
+            key.lookat,
+            self.vup,
+            key.vfov,
+            self.aspect_ratio,
+            self.aperture,
+            key.focus_dist,
+            time_0,
+            time_1,
+        );
+        camera.projection = self.projection;
+        camera
+    }
+
+    // The world is built once and shared by every frame, so its BVH bounds have to
+    // cover the time span of the whole sequence.
+    pub fn render_sequence(
+        &self,
+        world: &World,
+        background: &Color,
+        settings: &SequenceSettings,
+    ) -> ImageResult<()> {
+        let start = Instant::now();
+
+        for frame in settings.frames.clone() {
+            let samples = settings.samples_for(frame);
+            println!(
+                "Frame {} ({}/{}) at {} samples per pixel",
+                frame,
+                frame - settings.frames.start + 1,
+                settings.frames.len(),
+                samples
+            );
+
+            // no output of its own, only the numbered frame gets written
+            let mut frame_settings =
+                RenderSettings::new(settings.width, samples, settings.max_depth);
+            frame_settings.denoise_settings = settings.denoise_settings;
+            let img = self
+                .camera(frame)
+                .settings_render(world, background, &frame_settings)?;
+            img.save(settings.frame_path(frame))?;
+        }
+
+        println!("Sequence finished in {:?}", start.elapsed());
+        Ok(())
+    }
+}
+
+pub struct SequenceSettings {
+    pub frames: Range<u32>,
+    // frames are written to `{output_prefix}{frame:04}.png`
+    pub output_prefix: String,
+    pub width: u32,
+    pub samples_per_pixel: u32,
+    // per frame overrides of `samples_per_pixel`
+    pub frame_samples: HashMap<u32, u32>,
+    pub max_depth: u32,
+    pub denoise_settings: Option<DenoiseSettings>,
+}
+
+impl SequenceSettings {
+    pub fn new(
+        frames: Range<u32>,
+        output_prefix: &str,
+        width: u32,
+        samples_per_pixel: u32,
+        max_depth: u32,
+    ) -> SequenceSettings {
+        SequenceSettings {
+            frames,
+            output_prefix: output_prefix.to_string(),
+            width,
+            samples_per_pixel,
+            frame_samples: HashMap::new(),
+            max_depth,
+            denoise_settings: None,
+        }
+    }
+
+    pub fn samples_for(&self, frame: u32) -> u32 {
+        *self
+            .frame_samples
+            .get(&frame)
+            .unwrap_or(&self.samples_per_pixel)
+    }
+
+    pub fn frame_path(&self, frame: u32) -> String {
+        format!("{}{:04}.png", self.output_prefix, frame)
+    }
+}
@@ -0,0 +1,437 @@
+use image::{ImageResult, RgbImage};
+
+use crate::color::{Color, BLACK, WHITE};
+use crate::exr::ExrImage;
+use crate::film::Film;
+use crate::materials::Tracable;
+use crate::ray::Ray;
+use crate::rendering::Camera;
+use crate::sampler::{hash, Sampler};
+use crate::vec3::Vec3;
+use crate::world::World;
+
+// Extra images filled in alongside the beauty render.
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Aov {
+    // distance from the camera along its view axis, infinite where nothing is hit
+    Depth,
+    Position,
+    // shading normal
+    Normal,
+    Uv,
+    Albedo,
+    // index of the object in the `World` plus one, 0 is the background
+    ObjectId,
+    // index of the material name in the `World` plus one, see `World::with_materials`
+    MaterialId,
+    // light that bounced once before reaching the camera
+    Direct,
+    // light that bounced more than once
+    Indirect,
+    // light sources and background seen straight from the camera
+    Emission,
+    // Cryptomatte mattes, see `Film::add_cryptomattes`
+    CryptoObject,
+    CryptoMaterial,
+}
+
+impl Aov {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Depth => "depth",
+            Aov::Position => "P",
+            Aov::Normal => "N",
+            Aov::Uv => "uv",
+            Aov::Albedo => "albedo",
+            Aov::ObjectId => "objectId",
+            Aov::MaterialId => "materialId",
+            Aov::Direct => "direct",
+            Aov::Indirect => "indirect",
+            Aov::Emission => "emission",
+            Aov::CryptoObject => "CryptoObject",
+            Aov::CryptoMaterial => "CryptoMaterial",
+        }
+    }
+
+    pub fn channels(&self) -> &'static [&'static str] {
+        match self {
+            Aov::Depth => &["Z"],
+            Aov::Position | Aov::Normal => &["X", "Y", "Z"],
+            Aov::Uv => &["U", "V"],
+            Aov::ObjectId | Aov::MaterialId => &["id"],
+            Aov::Albedo | Aov::Direct | Aov::Indirect | Aov::Emission => &["R", "G", "B"],
+            // written separately with their own layout
+            Aov::CryptoObject | Aov::CryptoMaterial => &[],
+        }
+    }
+
+    // Data passes are not blended across edges, every pixel keeps the value of the
+    // sample closest to its center.
+    pub fn is_data(&self) -> bool {
+        !matches!(
+            self,
+            Aov::Albedo
+                | Aov::Direct
+                | Aov::Indirect
+                | Aov::Emission
+                | Aov::CryptoObject
+                | Aov::CryptoMaterial
+        )
+    }
+
+    // Crypto passes keep the coverage of every id seen in a pixel.
+    pub fn is_crypto(&self) -> bool {
+        matches!(self, Aov::CryptoObject | Aov::CryptoMaterial)
+    }
+}
+
+// everything the AOVs need from one camera sample
+#[derive(Copy, Clone, Debug)]
+pub struct AovSample {
+    pub depth: f32,
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub uv: (f32, f32),
+    pub albedo: Color,
+    pub object_id: u32,
+    pub material_id: u32,
+    pub direct: Color,
+    pub indirect: Color,
+    pub emission: Color,
+}
+
+impl AovSample {
+    pub fn miss() -> AovSample {
+        AovSample {
+            depth: f32::INFINITY,
+            position: Vec3::zero(),
+            normal: Vec3::zero(),
+            uv: (0.0, 0.0),
+            albedo: BLACK,
+            object_id: 0,
+            material_id: 0,
+            direct: BLACK,
+            indirect: BLACK,
+            emission: BLACK,
+        }
+    }
+
+    pub fn beauty(&self) -> Color {
+        self.emission + self.direct + self.indirect
+    }
+
+    pub fn value(&self, aov: Aov) -> Color {
+        match aov {
+            Aov::Depth => Color::new(self.depth, self.depth, self.depth),
+            Aov::Position => Color::new(self.position.x, self.position.y, self.position.z),
+            Aov::Normal => Color::new(self.normal.x, self.normal.y, self.normal.z),
+            Aov::Uv => Color::new(self.uv.0, self.uv.1, 0.0),
+            Aov::Albedo => self.albedo,
+            // below 2^24 so the ids survive being stored as floats
+            Aov::ObjectId => Color::new(self.object_id as f32, 0.0, 0.0),
+            Aov::MaterialId => Color::new(self.material_id as f32, 0.0, 0.0),
+            Aov::Direct => self.direct,
+            Aov::Indirect => self.indirect,
+            Aov::Emission => self.emission,
+            Aov::CryptoObject | Aov::CryptoMaterial => BLACK,
+        }
+    }
+
+    pub fn id(&self, aov: Aov) -> u32 {
+        match aov {
+            Aov::ObjectId | Aov::CryptoObject => self.object_id,
+            Aov::MaterialId | Aov::CryptoMaterial => self.material_id,
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AovLayer {
+    pub aov: Aov,
+    values: Vec<Color>,
+    // filter weight, or for data passes the squared distance of the kept sample
+    weight: Vec<f32>,
+    // crypto passes only, the filter weight of every id in a pixel
+    ranks: Vec<Vec<(u32, f32)>>,
+}
+
+impl AovLayer {
+    pub fn new(aov: Aov, pixels: usize) -> AovLayer {
+        let weight = if aov.is_data() { f32::INFINITY } else { 0.0 };
+        let rank_pixels = if aov.is_crypto() { pixels } else { 0 };
+        AovLayer {
+            aov,
+            values: vec![BLACK; pixels],
+            weight: vec![weight; pixels],
+            ranks: vec![Vec::new(); rank_pixels],
+        }
+    }
+
+    // `w` is the filter weight and `d2` the squared distance to the pixel center
+    pub fn add(&mut self, i: usize, w: f32, d2: f32, sample: &AovSample) {
+        if self.aov.is_crypto() {
+            self.weight[i] += w;
+            let id = sample.id(self.aov);
+            // the background gets no matte
+            if id == 0 {
+                return;
+            }
+            match self.ranks[i].iter_mut().find(|(rank, _)| *rank == id) {
+                Some((_, weight)) => *weight += w,
+                None => self.ranks[i].push((id, w)),
+            }
+        } else if self.aov.is_data() {
+            if d2 < self.weight[i] {
+                self.values[i] = sample.value(self.aov);
+                self.weight[i] = d2;
+            }
+        } else {
+            self.values[i] = self.values[i] + sample.value(self.aov) * w;
+            self.weight[i] += w;
+        }
+    }
+
+    pub fn merge_pixel(&mut self, dst: usize, other: &AovLayer, src: usize) {
+        if self.aov.is_crypto() {
+            self.weight[dst] += other.weight[src];
+            for &(id, w) in other.ranks[src].iter() {
+                match self.ranks[dst].iter_mut().find(|(rank, _)| *rank == id) {
+                    Some((_, weight)) => *weight += w,
+                    None => self.ranks[dst].push((id, w)),
+                }
+            }
+        } else if self.aov.is_data() {
+            if other.weight[src] < self.weight[dst] {
+                self.values[dst] = other.values[src];
+                self.weight[dst] = other.weight[src];
+            }
+        } else {
+            self.values[dst] = self.values[dst] + other.values[src];
+            self.weight[dst] += other.weight[src];
+        }
+    }
+
+    // ids in a pixel with the fraction of it they cover, most coverage first
+    pub fn coverage(&self, i: usize) -> Vec<(u32, f32)> {
+        if self.weight[i] <= 0.0 {
+            return Vec::new();
+        }
+        let mut ranks: Vec<(u32, f32)> = self.ranks[i]
+            .iter()
+            .map(|&(id, w)| (id, w / self.weight[i]))
+            .collect();
+        ranks.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap().then(a.0.cmp(&b.0)));
+        ranks
+    }
+
+    // every id a crypto pass has seen
+    pub fn ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.ranks.iter().flatten().map(|&(id, _)| id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    pub fn value(&self, i: usize) -> Color {
+        if self.aov.is_crypto() {
+            BLACK
+        } else if self.aov.is_data() {
+            self.values[i]
+        } else if self.weight[i] > 0.0 {
+            self.values[i] / self.weight[i]
+        } else {
+            BLACK
+        }
+    }
+}
+
+fn id_color(id: u32) -> Color {
+    if id == 0 {
+        return BLACK;
+    }
+    let h = hash(&[id as u64]);
+    Color::from_rgb(h as u8, (h >> 8) as u8, (h >> 16) as u8)
+}
+
+impl Camera {
+    // Same path as `Ray::bvh2_color`, with the light split up by the bounce it was
+    // picked up on and the first hit recorded for the data passes.
+    pub fn trace_aovs(
+        &self,
+        ray: Ray,
+        world: &World,
+        background: &Color,
+        max_depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> AovSample {
+        let mut sample = AovSample::miss();
+        let mut throughput = WHITE;
+        let mut ray = ray;
+        let mut bsdf_pdf = None;
+
+        for bounce in 0..max_depth {
+            let light = match world.intersects(&ray, 0.001, f32::MAX, sampler) {
+                None => throughput * *background,
+                Some(i) => {
+                    if bounce == 0 {
+                        sample.depth = (i.point - self.origin).dot(&-self.w);
+                        sample.position = i.point;
+                        sample.normal = i.normal;
+                        sample.uv = i.uv;
+                        sample.albedo = i.mat.albedo(i.uv, i.point);
+                        sample.object_id = i.object_id + 1;
+                        sample.material_id = world.material_id(i.object_id);
+                    }
+
+                    let emitted =
+                        throughput * ray.emission_weight(world, bsdf_pdf) * i.mat.emitted(i.uv, &i);
+                    // light sampled from here has one more bounce in it than this hit
+                    let direct = throughput * ray.direct_light(world, &i, sampler);
+                    match bounce {
+                        0 => sample.direct = sample.direct + direct,
+                        _ => sample.indirect = sample.indirect + direct,
+                    }
+
+                    match i.mat.scatter(&ray, &i, sampler) {
+                        Some((attenuation, scattered)) => {
+                            let scattered = scattered.inherit_wavelength(&ray);
+                            bsdf_pdf = ray.bsdf_pdf(world, &i, &scattered);
+                            throughput = throughput * attenuation;
+                            ray = scattered;
+                            match bounce {
+                                0 => sample.emission = sample.emission + emitted,
+                                1 => sample.direct = sample.direct + emitted,
+                                _ => sample.indirect = sample.indirect + emitted,
+                            }
+                            continue;
+                        }
+                        None => emitted,
+                    }
+                }
+            };
+
+            match bounce {
+                0 => sample.emission = sample.emission + light,
+                1 => sample.direct = sample.direct + light,
+                _ => sample.indirect = sample.indirect + light,
+            }
+            break;
+        }
+
+        sample
+    }
+}
+
+impl Film {
+    pub fn layer(&self, aov: Aov) -> Option<&AovLayer> {
+        self.layers.iter().find(|l| l.aov == aov)
+    }
+
+    // 8 bit preview of an AOV, data is scaled to fit and ids get random colors
+    pub fn aov_image(&self, aov: Aov) -> Option<RgbImage> {
+        let layer = self.layer(aov)?;
+        let n = (self.width * self.height) as usize;
+
+        // ids blended by how much of the pixel they cover
+        if aov.is_crypto() {
+            let raw: Vec<u8> = (0..n)
+                .flat_map(|i| {
+                    let coverage = layer.coverage(i);
+                    let c = coverage
+                        .iter()
+                        .fold(BLACK, |c, &(id, w)| c + id_color(id) * w);
+                    c.to_slice()
+                })
+                .collect();
+            return RgbImage::from_raw(self.width, self.height, raw);
+        }
+        let values: Vec<Color> = (0..n).map(|i| layer.value(i)).collect();
+
+        let (mut min, mut max) = (Vec3::from_one(f32::MAX), Vec3::from_one(f32::MIN));
+        for v in values.iter().filter(|v| v.r.is_finite()) {
+            let v = Vec3::new(v.r, v.g, v.b);
+            for a in 0..3 {
+                min[a] = min[a].min(v[a]);
+                max[a] = max[a].max(v[a]);
+            }
+        }
+        let range = max - min;
+
+        let pixels = values.iter().map(|v| match aov {
+            Aov::Depth if !v.r.is_finite() => BLACK,
+            Aov::Depth => {
+                let d = v.r / max.x.max(1e-6);
+                Color::new(d, d, d)
+            }
+            Aov::Position => Color::new(
+                (v.r - min.x) / range.x.max(1e-6),
+                (v.g - min.y) / range.y.max(1e-6),
+                (v.b - min.z) / range.z.max(1e-6),
+            ),
+            Aov::Normal => Color::new(0.5 * (v.r + 1.0), 0.5 * (v.g + 1.0), 0.5 * (v.b + 1.0)),
+            Aov::Uv => *v,
+            Aov::ObjectId | Aov::MaterialId => id_color(v.r as u32),
+            Aov::CryptoObject | Aov::CryptoMaterial => BLACK,
+            Aov::Albedo | Aov::Direct | Aov::Indirect | Aov::Emission => v.sqrt(),
+        });
+
+        let raw: Vec<u8> = pixels.flat_map(|c| c.to_slice()).collect();
+        RgbImage::from_raw(self.width, self.height, raw)
+    }
+
+    // writes every AOV to `{prefix}{name}.png`
+    pub fn save_aovs(&self, prefix: &str) -> ImageResult<()> {
+        for layer in self.layers.iter() {
+            if let Some(img) = self.aov_image(layer.aov) {
+                img.save(format!("{}{}.png", prefix, layer.aov.name()))?;
+            }
+        }
+        Ok(())
+    }
+
+    // linear beauty in R, G, B and every AOV as its own layer
+    pub fn to_exr(&self) -> ExrImage {
+        let n = (self.width * self.height) as usize;
+        let mut exr = ExrImage::new(self.width, self.height);
+
+        let beauty: Vec<Color> = (0..n)
+            .map(|i| self.color(i as u32 % self.width, i as u32 / self.width))
+            .collect();
+        exr.add_channel("R", beauty.iter().map(|c| c.r).collect());
+        exr.add_channel("G", beauty.iter().map(|c| c.g).collect());
+        exr.add_channel("B", beauty.iter().map(|c| c.b).collect());
+
+        for layer in self.layers.iter() {
+            let values: Vec<Color> = (0..n).map(|i| layer.value(i)).collect();
+            for (k, channel) in layer.aov.channels().iter().enumerate() {
+                let name = format!("{}.{}", layer.aov.name(), channel);
+                let component = |c: &Color| match k {
+                    0 => c.r,
+                    1 => c.g,
+                    _ => c.b,
+                };
+                exr.add_channel(&name, values.iter().map(component).collect());
+            }
+        }
+
+        exr
+    }
+
+    // albedo and normal buffers for the denoiser, when both AOVs were rendered
+    pub fn denoise_buffers(&self) -> Option<(Vec<f32>, Vec<f32>)> {
+        let albedo = self.layer(Aov::Albedo)?;
+        let normal = self.layer(Aov::Normal)?;
+        let n = (self.width * self.height) as usize;
+
+        let albedo_buffer = (0..n).flat_map(|i| albedo.value(i).to_vec_f32()).collect();
+        let normal_buffer = (0..n)
+            .flat_map(|i| {
+                let c = normal.value(i);
+                vec![c.r, c.g, c.b]
+            })
+            .collect();
+        Some((albedo_buffer, normal_buffer))
+    }
+}
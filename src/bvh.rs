@@ -1,5 +1,4 @@
 extern crate rand;
-use rand::Rng;
 use std::cmp::Ordering;
 use std::fmt;
 
@@ -7,6 +6,7 @@ use crate::aabb::{surrounding_box, Aabb};
 use crate::intersection::Intersection;
 use crate::objects::Object;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 
 #[derive(Debug)]
 pub struct BvhTree<'a> {
@@ -28,12 +28,19 @@ pub struct NodeId {
 }
 
 impl<'a> BvhTree<'a> {
-    fn intersects(&self, id: NodeId, r: &Ray, tmin: f32, tmax: f32) -> Option<Intersection> {
+    fn intersects(
+        &self,
+        id: NodeId,
+        r: &Ray,
+        tmin: f32,
+        tmax: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
         let node = &self.nodes[id.index];
 
         if node.aabb.is_none() || node.aabb.is_some() && node.aabb.unwrap().hit(r, tmin, tmax) {
             match node.object {
-                Some(ref object) => return object.intersects(r, tmin, tmax),
+                Some(ref object) => return object.intersects(r, tmin, tmax, sampler),
                 None => {}
             }
 
@@ -41,11 +48,11 @@ impl<'a> BvhTree<'a> {
             let mut hit_right: Option<Intersection> = None;
 
             if let Some(ref left_index) = node.left {
-                hit_left = self.intersects(*left_index, r, tmin, tmax);
+                hit_left = self.intersects(*left_index, r, tmin, tmax, sampler);
             }
 
             if let Some(ref right_index) = node.right {
-                hit_right = self.intersects(*right_index, r, tmin, tmax);
+                hit_right = self.intersects(*right_index, r, tmin, tmax, sampler);
             }
 
             match hit_left {
@@ -94,7 +101,7 @@ impl<'a> BvhTree<'a> {
     }
 
     fn build(&mut self, l: &'a mut [Object]) -> NodeId {
-        let axis = rand::thread_rng().gen_range(0..3);
+        let axis = longest_axis(l);
 
         match axis {
             0 => l.sort_by(|a, b| box_x_compare(a, b)),
@@ -138,7 +145,7 @@ impl<'a> BvhTree<'a> {
         self.nodes.push(BvhNode {
             left: None,
             right: None,
-            aabb: object.bounding_box(),
+            aabb: object.bounding_box(0.0, 1.0),
             object: Some(object),
         });
 
@@ -179,8 +186,14 @@ impl<'a> BvhTree<'a> {
         self.nodes[self.root.index].aabb
     }
 
-    pub fn hit(&self, r: &Ray, tmin: f32, tmax: f32) -> Option<Intersection> {
-        self.intersects(self.root, r, tmin, tmax)
+    pub fn hit(
+        &self,
+        r: &Ray,
+        tmin: f32,
+        tmax: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
+        self.intersects(self.root, r, tmin, tmax, sampler)
     }
 }
 
@@ -195,9 +208,31 @@ impl<'a> fmt::Display for BvhTree<'a> {
     }
 }
 
+// split along the axis the objects are spread out the most on
+fn longest_axis(l: &[Object]) -> usize {
+    let bbox = l
+        .iter()
+        .filter_map(|o| o.bounding_box(0.0, 1.0))
+        .reduce(|a, b| surrounding_box(&a, &b));
+
+    match bbox {
+        Some(bbox) => {
+            let d = bbox.max - bbox.min;
+            if d.x >= d.y && d.x >= d.z {
+                0
+            } else if d.y >= d.z {
+                1
+            } else {
+                2
+            }
+        }
+        None => 0,
+    }
+}
+
 fn box_x_compare(a: &Object, b: &Object) -> Ordering {
-    if let Some(box_left) = a.bounding_box() {
-        if let Some(box_right) = b.bounding_box() {
+    if let Some(box_left) = a.bounding_box(0.0, 1.0) {
+        if let Some(box_right) = b.bounding_box(0.0, 1.0) {
             if let Some(cmp) = box_left.min.x.partial_cmp(&box_right.min.x) {
                 return cmp;
             } else {
@@ -210,8 +245,8 @@ fn box_x_compare(a: &Object, b: &Object) -> Ordering {
 }
 
 fn box_y_compare(a: &Object, b: &Object) -> Ordering {
-    if let Some(box_left) = a.bounding_box() {
-        if let Some(box_right) = b.bounding_box() {
+    if let Some(box_left) = a.bounding_box(0.0, 1.0) {
+        if let Some(box_right) = b.bounding_box(0.0, 1.0) {
             if let Some(cmp) = box_left.min.y.partial_cmp(&box_right.min.y) {
                 return cmp;
             } else {
@@ -224,8 +259,8 @@ fn box_y_compare(a: &Object, b: &Object) -> Ordering {
 }
 
 fn box_z_compare(a: &Object, b: &Object) -> Ordering {
-    if let Some(box_left) = a.bounding_box() {
-        if let Some(box_right) = b.bounding_box() {
+    if let Some(box_left) = a.bounding_box(0.0, 1.0) {
+        if let Some(box_right) = b.bounding_box(0.0, 1.0) {
             if let Some(cmp) = box_left.min.z.partial_cmp(&box_right.min.z) {
                 return cmp;
             } else {
@@ -3,13 +3,17 @@ use crate::aabb::Aabb;
 use crate::intersection::Intersection;
 use crate::objects::Object;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use std::cmp::Ordering;
 use std::f32;
 
+// an object and the id its intersections are tagged with
+type Tagged = (u32, Box<Object>);
+
 #[derive(Clone, Debug)]
 enum BVHNode {
     Branch { left: Box<BVH>, right: Box<BVH> },
-    Leaf(Box<Object>),
+    Leaf { id: u32, object: Box<Object> },
 }
 
 #[derive(Clone, Debug)]
@@ -19,16 +23,28 @@ pub struct BVH {
 }
 
 impl BVH {
-    pub fn new(mut hitable: Vec<Box<Object>>, time0: f32, time1: f32) -> Self {
+    pub fn new(hitable: Vec<Box<Object>>, time0: f32, time1: f32) -> Self {
+        BVH::with_ids(
+            hitable
+                .into_iter()
+                .enumerate()
+                .map(|(id, o)| (id as u32, o))
+                .collect(),
+            time0,
+            time1,
+        )
+    }
+
+    // every intersection with a leaf gets tagged with the id it was given here
+    pub fn with_ids(mut hitable: Vec<Tagged>, time0: f32, time1: f32) -> Self {
         fn box_compare(
-            _time0: f32,
-            _time1: f32,
+            time0: f32,
+            time1: f32,
             axis: usize,
-        ) -> impl FnMut(&Box<Object>, &Box<Object>) -> Ordering {
-            move |a, b| {
-                // removed time from bounding box
-                let a_bbox = a.bounding_box();
-                let b_bbox = b.bounding_box();
+        ) -> impl FnMut(&Tagged, &Tagged) -> Ordering {
+            move |(_, a), (_, b)| {
+                let a_bbox = a.bounding_box(time0, time1);
+                let b_bbox = b.bounding_box(time0, time1);
                 if let (Some(a), Some(b)) = (a_bbox, b_bbox) {
                     let ac = a.min[axis] + a.max[axis];
                     let bc = b.min[axis] + b.max[axis];
@@ -39,11 +55,11 @@ impl BVH {
             }
         }
 
-        fn axis_range(hitable: &Vec<Box<Object>>, _time0: f32, _time1: f32, axis: usize) -> f32 {
+        fn axis_range(hitable: &Vec<Tagged>, time0: f32, time1: f32, axis: usize) -> f32 {
             let (min, max) = hitable
                 .iter()
-                .fold((f32::MAX, f32::MIN), |(bmin, bmax), hit| {
-                    if let Some(aabb) = hit.bounding_box() {
+                .fold((f32::MAX, f32::MIN), |(bmin, bmax), (_, hit)| {
+                    if let Some(aabb) = hit.bounding_box(time0, time1) {
                         (bmin.min(aabb.min[axis]), bmax.max(aabb.max[axis]))
                     } else {
                         (bmin, bmax)
@@ -65,10 +81,10 @@ impl BVH {
         match len {
             0 => panic!["no elements in scene"],
             1 => {
-                let leaf = hitable.pop().unwrap();
-                if let Some(bbox) = leaf.bounding_box() {
+                let (id, object) = hitable.pop().unwrap();
+                if let Some(bbox) = object.bounding_box(time0, time1) {
                     BVH {
-                        tree: BVHNode::Leaf(leaf),
+                        tree: BVHNode::Leaf { id, object },
                         bbox,
                     }
                 } else {
@@ -76,8 +92,8 @@ impl BVH {
                 }
             }
             _ => {
-                let right = BVH::new(hitable.drain(len / 2..).collect(), time0, time1);
-                let left = BVH::new(hitable, time0, time1);
+                let right = BVH::with_ids(hitable.drain(len / 2..).collect(), time0, time1);
+                let left = BVH::with_ids(hitable, time0, time1);
                 let bbox = aabb::surrounding_box(&left.bbox, &right.bbox);
                 BVH {
                     tree: BVHNode::Branch {
@@ -92,16 +108,27 @@ impl BVH {
 }
 
 impl BVH {
-    pub fn intersects(&self, ray: &Ray, t_min: f32, mut t_max: f32) -> Option<Intersection> {
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        mut t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
         if self.bbox.hit(&ray, t_min, t_max) {
             match &self.tree {
-                BVHNode::Leaf(leaf) => leaf.intersects(&ray, t_min, t_max),
+                BVHNode::Leaf { id, object } => object
+                    .intersects_opaque(&ray, t_min, t_max, sampler)
+                    .map(|mut hit| {
+                        hit.object_id = *id;
+                        hit
+                    }),
                 BVHNode::Branch { left, right } => {
-                    let left = left.intersects(&ray, t_min, t_max);
+                    let left = left.intersects(&ray, t_min, t_max, sampler);
                     if let Some(l) = &left {
                         t_max = l.distance
                     };
-                    let right = right.intersects(&ray, t_min, t_max);
+                    let right = right.intersects(&ray, t_min, t_max, sampler);
                     if right.is_some() {
                         right
                     } else {
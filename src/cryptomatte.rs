@@ -0,0 +1,125 @@
+use crate::aov::Aov;
+use crate::exr::ExrImage;
+use crate::film::Film;
+use crate::world::World;
+
+// ranks written per matte, two fit in every RGBA layer
+pub const LEVELS: usize = 6;
+
+// MurmurHash3 x86 32 bit, the hash Cryptomatte stores names as
+pub fn murmur3_32(data: &[u8], seed: u32) -> u32 {
+    const C1: u32 = 0xcc9e_2d51;
+    const C2: u32 = 0x1b87_3593;
+
+    let mut h = seed;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+
+    for chunk in chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+        h = h.rotate_left(13).wrapping_mul(5).wrapping_add(0xe654_6b64);
+    }
+
+    if !tail.is_empty() {
+        let mut k = 0u32;
+        for (i, &b) in tail.iter().enumerate() {
+            k |= (b as u32) << (8 * i);
+        }
+        k = k.wrapping_mul(C1).rotate_left(15).wrapping_mul(C2);
+        h ^= k;
+    }
+
+    h ^= data.len() as u32;
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h
+}
+
+// The hash of `name` as it is stored in the image. Hashes that would read as a
+// denormal, infinity or NaN get an exponent bit flipped.
+pub fn name_hash(name: &str) -> u32 {
+    let h = murmur3_32(name.as_bytes(), 0);
+    let exponent = (h >> 23) & 0xff;
+    if exponent == 0 || exponent == 0xff {
+        h ^ (1 << 23)
+    } else {
+        h
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+// {"name": "hash in hex", ...}
+fn manifest(names: &[String]) -> String {
+    let entries: Vec<String> = names
+        .iter()
+        .map(|name| format!("{}:\"{:08x}\"", json_string(name), name_hash(name)))
+        .collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+impl Film {
+    // Adds every crypto AOV as `{type}00.RGBA`, `{type}01.RGBA`, ... with the ids
+    // in R and B and their coverage in G and A, most coverage first. Object and
+    // material names come from `world`.
+    pub fn add_cryptomattes(&self, exr: &mut ExrImage, world: &World) {
+        let n = (self.width * self.height) as usize;
+
+        for layer in self.layers.iter().filter(|l| l.aov.is_crypto()) {
+            let type_name = layer.aov.name();
+            let name = |id: u32| match layer.aov {
+                Aov::CryptoObject => world.names[id as usize - 1].clone(),
+                _ => world.materials[id as usize - 1].clone(),
+            };
+
+            // hashing once per id instead of once per pixel and rank
+            let ids = layer.ids();
+            let names: Vec<String> = ids.iter().map(|&id| name(id)).collect();
+            let hashes: Vec<f32> = names
+                .iter()
+                .map(|name| f32::from_bits(name_hash(name)))
+                .collect();
+
+            let pixels: Vec<Vec<(u32, f32)>> = (0..n).map(|i| layer.coverage(i)).collect();
+            // (id, coverage) channel pairs, one pair per rank
+            let channels = (0..LEVELS).flat_map(|rank| {
+                let ranks = pixels.iter().map(move |ranks| ranks.get(rank));
+                let id = ranks
+                    .clone()
+                    .map(|r| r.map_or(0.0, |(id, _)| hashes[ids.binary_search(id).unwrap()]))
+                    .collect::<Vec<f32>>();
+                let coverage = ranks.map(|r| r.map_or(0.0, |r| r.1)).collect();
+                vec![id, coverage]
+            });
+
+            for (c, values) in channels.into_iter().enumerate() {
+                let channel = ["R", "G", "B", "A"][c % 4];
+                exr.add_channel(&format!("{}{:02}.{}", type_name, c / 4, channel), values);
+            }
+
+            let key = &format!("{:08x}", murmur3_32(type_name.as_bytes(), 0))[..7];
+            let prefix = format!("cryptomatte/{}/", key);
+            exr.add_attribute(&format!("{}name", prefix), type_name);
+            exr.add_attribute(&format!("{}hash", prefix), "MurmurHash3_32");
+            exr.add_attribute(&format!("{}conversion", prefix), "uint32_to_float32");
+            exr.add_attribute(&format!("{}manifest", prefix), &manifest(&names));
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// Minimal OpenEXR writer: scanline image, no compression, 32 bit float channels.
+// Channel names follow the `layer.channel` convention so compositors group them.
+pub struct ExrImage {
+    pub width: u32,
+    pub height: u32,
+    channels: Vec<(String, Vec<f32>)>,
+    // extra string attributes written into the header
+    attributes: Vec<(String, String)>,
+}
+
+const FLOAT: i32 = 2;
+
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    out.extend_from_slice(name.as_bytes());
+    out.push(0);
+    out.extend_from_slice(kind.as_bytes());
+    out.push(0);
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+impl ExrImage {
+    pub fn new(width: u32, height: u32) -> ExrImage {
+        ExrImage {
+            width,
+            height,
+            channels: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+
+    // `values` holds one value per pixel, rows from the top
+    pub fn add_channel(&mut self, name: &str, values: Vec<f32>) {
+        assert_eq!(values.len(), (self.width * self.height) as usize);
+        self.channels.push((name.to_string(), values));
+    }
+
+    pub fn add_attribute(&mut self, name: &str, value: &str) {
+        self.attributes.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn write(&mut self, path: &str) -> io::Result<()> {
+        // the format wants channels sorted by name
+        self.channels.sort_by(|a, b| a.0.cmp(&b.0));
+        let (w, h) = (self.width as i32, self.height as i32);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&[0x76, 0x2f, 0x31, 0x01]);
+        // version 2, with the long names flag when a name does not fit in 31 bytes
+        let mut names = self.channels.iter().map(|c| &c.0);
+        let long_names = names.any(|name| name.len() > 31)
+            || self.attributes.iter().any(|(name, _)| name.len() > 31);
+        let flags = if long_names { 0x400 } else { 0 };
+        header.extend_from_slice(&(2u32 | flags).to_le_bytes());
+
+        let mut chlist = Vec::new();
+        for (name, _) in self.channels.iter() {
+            chlist.extend_from_slice(name.as_bytes());
+            chlist.push(0);
+            chlist.extend_from_slice(&FLOAT.to_le_bytes());
+            chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+        }
+        chlist.push(0);
+        attribute(&mut header, "channels", "chlist", &chlist);
+
+        attribute(&mut header, "compression", "compression", &[0]);
+
+        let window: Vec<u8> = [0, 0, w - 1, h - 1]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        attribute(&mut header, "dataWindow", "box2i", &window);
+        attribute(&mut header, "displayWindow", "box2i", &window);
+        attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1.0f32.to_le_bytes(),
+        );
+        attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1.0f32.to_le_bytes(),
+        );
+        for (name, value) in self.attributes.iter() {
+            attribute(&mut header, name, "string", value.as_bytes());
+        }
+        header.push(0);
+
+        // one scanline per chunk, each chunk is its y, its size and then every channel
+        let line_size = self.channels.len() * self.width as usize * 4;
+        let chunk_size = (8 + line_size) as u64;
+        let table_end = header.len() as u64 + 8 * self.height as u64;
+
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&header)?;
+        for y in 0..self.height as u64 {
+            out.write_all(&(table_end + y * chunk_size).to_le_bytes())?;
+        }
+
+        for y in 0..self.height as usize {
+            out.write_all(&(y as i32).to_le_bytes())?;
+            out.write_all(&(line_size as i32).to_le_bytes())?;
+            for (_, values) in self.channels.iter() {
+                let row = &values[y * self.width as usize..(y + 1) * self.width as usize];
+                for v in row {
+                    out.write_all(&v.to_le_bytes())?;
+                }
+            }
+        }
+
+        out.flush()
+    }
+}
@@ -0,0 +1,205 @@
+use image::RgbImage;
+
+use crate::aov::{Aov, AovLayer, AovSample};
+use crate::color::{Color, BLACK};
+use crate::filter::{Filter, Filterable};
+use crate::tiles::Tile;
+
+// share of a pixel's total absolute filter weight below which what is left of it
+// after negative lobes counts as cancelled out
+const MIN_WEIGHT: f32 = 0.05;
+
+// Accumulates filtered radiance samples per pixel. Pixel (0, 0) is the top left.
+#[derive(Clone, Debug)]
+pub struct Film {
+    pub width: u32,
+    pub height: u32,
+    // where this film's top left pixel sits in the image, tile films start
+    // outside of their tile so samples can spill over its edges
+    pub x0: i32,
+    pub y0: i32,
+    sum: Vec<Color>,
+    weight: Vec<f32>,
+    abs_weight: Vec<f32>,
+    // unfiltered sum and count of the samples that landed inside each pixel
+    box_sum: Vec<Color>,
+    box_count: Vec<u32>,
+    // camera samples taken per pixel
+    samples: Vec<u32>,
+    pub layers: Vec<AovLayer>,
+}
+
+impl Film {
+    pub fn new(width: u32, height: u32) -> Film {
+        let n = (width * height) as usize;
+        Film {
+            width,
+            height,
+            x0: 0,
+            y0: 0,
+            sum: vec![BLACK; n],
+            weight: vec![0.0; n],
+            abs_weight: vec![0.0; n],
+            box_sum: vec![BLACK; n],
+            box_count: vec![0; n],
+            samples: vec![0; n],
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn with_aovs(width: u32, height: u32, aovs: &[Aov]) -> Film {
+        let mut film = Film::new(width, height);
+        let n = (width * height) as usize;
+        film.layers = aovs.iter().map(|&aov| AovLayer::new(aov, n)).collect();
+        film
+    }
+
+    // `tile` with `margin` extra pixels on every side
+    pub fn for_tile(tile: &Tile, margin: u32, aovs: &[Aov]) -> Film {
+        let (width, height) = (tile.width + 2 * margin, tile.height + 2 * margin);
+        let mut film = Film::with_aovs(width, height, aovs);
+        film.x0 = tile.x as i32 - margin as i32;
+        film.y0 = tile.y as i32 - margin as i32;
+        film
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    // pixels whose center is within the filter's radius of (px, py), as
+    // (x_min, x_max, y_min, y_max) in film coordinates
+    fn footprint(&self, r: f32, lx: f32, ly: f32) -> Option<(u32, u32, u32, u32)> {
+        let x_min = (lx - r - 0.5).ceil().max(0.0) as u32;
+        let y_min = (ly - r - 0.5).ceil().max(0.0) as u32;
+        let x_max = (lx + r - 0.5).floor().min(self.width as f32 - 1.0);
+        let y_max = (ly + r - 0.5).floor().min(self.height as f32 - 1.0);
+        if x_max < 0.0 || y_max < 0.0 {
+            return None;
+        }
+        Some((x_min, x_max as u32, y_min, y_max as u32))
+    }
+
+    // adds a sample at (px, py) in image pixel coordinates to every pixel whose
+    // center is within the filter's radius
+    pub fn splat(&mut self, filter: &Filter, px: f32, py: f32, color: Color) {
+        let (lx, ly) = (px - self.x0 as f32, py - self.y0 as f32);
+        if lx >= 0.0 && ly >= 0.0 && lx < self.width as f32 && ly < self.height as f32 {
+            let i = self.index(lx as u32, ly as u32);
+            self.box_sum[i] = self.box_sum[i] + color;
+            self.box_count[i] += 1;
+        }
+
+        let (x_min, x_max, y_min, y_max) = match self.footprint(filter.radius(), lx, ly) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let w = filter.evaluate(x as f32 + 0.5 - lx, y as f32 + 0.5 - ly);
+                if w != 0.0 {
+                    let i = self.index(x, y);
+                    self.sum[i] = self.sum[i] + color * w;
+                    self.weight[i] += w;
+                    self.abs_weight[i] += w.abs();
+                }
+            }
+        }
+    }
+
+    // same footprint as `splat`, for the AOV layers
+    pub fn splat_aovs(&mut self, filter: &Filter, px: f32, py: f32, sample: &AovSample) {
+        let (lx, ly) = (px - self.x0 as f32, py - self.y0 as f32);
+        let (x_min, x_max, y_min, y_max) = match self.footprint(filter.radius(), lx, ly) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let (dx, dy) = (x as f32 + 0.5 - lx, y as f32 + 0.5 - ly);
+                let w = filter.evaluate(dx, dy);
+                if w != 0.0 {
+                    let i = self.index(x, y);
+                    for layer in self.layers.iter_mut() {
+                        layer.add(i, w, dx * dx + dy * dy, sample);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn count_samples(&mut self, x: u32, y: u32, n: u32) {
+        let i = self.index(x, y);
+        self.samples[i] += n;
+    }
+
+    pub fn samples(&self, x: u32, y: u32) -> u32 {
+        self.samples[self.index(x, y)]
+    }
+
+    // Negative filter lobes can cancel out most of a pixel's weight, and dividing
+    // by what is left blows the pixel up. Those fall back to the plain average of
+    // the samples taken inside the pixel.
+    pub fn color(&self, x: u32, y: u32) -> Color {
+        let i = self.index(x, y);
+        let count = self.box_count[i] as f32;
+        if self.weight[i] > MIN_WEIGHT * self.abs_weight[i] && self.weight[i] > 0.0 {
+            self.sum[i] / self.weight[i]
+        } else if count > 0.0 {
+            self.box_sum[i] / count
+        } else {
+            BLACK
+        }
+    }
+
+    // adds the part of `other` that overlaps this film
+    pub fn merge(&mut self, other: &Film) {
+        for y in 0..other.height {
+            for x in 0..other.width {
+                let tx = other.x0 + x as i32 - self.x0;
+                let ty = other.y0 + y as i32 - self.y0;
+                if tx < 0 || ty < 0 || tx >= self.width as i32 || ty >= self.height as i32 {
+                    continue;
+                }
+
+                let (src, dst) = (other.index(x, y), self.index(tx as u32, ty as u32));
+                self.sum[dst] = self.sum[dst] + other.sum[src];
+                self.weight[dst] += other.weight[src];
+                self.abs_weight[dst] += other.abs_weight[src];
+                self.box_sum[dst] = self.box_sum[dst] + other.box_sum[src];
+                self.box_count[dst] += other.box_count[src];
+                self.samples[dst] += other.samples[src];
+                for (layer, other) in self.layers.iter_mut().zip(other.layers.iter()) {
+                    layer.merge_pixel(dst, other, src);
+                }
+            }
+        }
+    }
+
+    // gamma 2 like the rest of the renderer
+    pub fn to_image(&self) -> RgbImage {
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let c = self.color(x, y).sqrt();
+            image::Rgb([
+                (255.99 * c.r).min(255.0) as u8,
+                (255.99 * c.g).min(255.0) as u8,
+                (255.99 * c.b).min(255.0) as u8,
+            ])
+        })
+    }
+
+    // samples per pixel from blue (fewest) to red (most)
+    pub fn heatmap(&self) -> RgbImage {
+        let min = *self.samples.iter().min().unwrap_or(&0) as f32;
+        let max = *self.samples.iter().max().unwrap_or(&0) as f32;
+        let range = (max - min).max(1.0);
+
+        RgbImage::from_fn(self.width, self.height, |x, y| {
+            let t = (self.samples(x, y) as f32 - min) / range;
+            let c = Color::new(t, 1.0 - (2.0 * t - 1.0).abs(), 1.0 - t);
+            image::Rgb(c.to_slice())
+        })
+    }
+}
@@ -0,0 +1,196 @@
+use std::f32::consts::PI;
+
+// Weight of a sample at offset (x, y) in pixels from a pixel's center. Samples
+// further than `radius` away on either axis do not count.
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum Filter {
+    Box(BoxFilter),
+    Tent(TentFilter),
+    Gaussian(GaussianFilter),
+    Mitchell(MitchellFilter),
+    Lanczos(LanczosFilter),
+}
+
+pub trait Filterable {
+    fn radius(&self) -> f32;
+    fn evaluate(&self, x: f32, y: f32) -> f32;
+}
+
+impl Filterable for Filter {
+    fn radius(&self) -> f32 {
+        match *self {
+            Filter::Box(ref f) => f.radius(),
+            Filter::Tent(ref f) => f.radius(),
+            Filter::Gaussian(ref f) => f.radius(),
+            Filter::Mitchell(ref f) => f.radius(),
+            Filter::Lanczos(ref f) => f.radius(),
+        }
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        match *self {
+            Filter::Box(ref f) => f.evaluate(x, y),
+            Filter::Tent(ref f) => f.evaluate(x, y),
+            Filter::Gaussian(ref f) => f.evaluate(x, y),
+            Filter::Mitchell(ref f) => f.evaluate(x, y),
+            Filter::Lanczos(ref f) => f.evaluate(x, y),
+        }
+    }
+}
+
+// a radius of 0.5 keeps every sample inside its own pixel
+#[derive(Copy, Clone, Debug)]
+pub struct BoxFilter {
+    pub radius: f32,
+}
+
+impl BoxFilter {
+    pub fn new(radius: f32) -> Filter {
+        Filter::Box(BoxFilter { radius })
+    }
+}
+
+impl Filterable for BoxFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        if x.abs() <= self.radius && y.abs() <= self.radius {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TentFilter {
+    pub radius: f32,
+}
+
+impl TentFilter {
+    pub fn new(radius: f32) -> Filter {
+        Filter::Tent(TentFilter { radius })
+    }
+}
+
+impl Filterable for TentFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        (self.radius - x.abs()).max(0.0) * (self.radius - y.abs()).max(0.0)
+    }
+}
+
+// shifted down so it reaches zero at the radius, larger alpha is narrower
+#[derive(Copy, Clone, Debug)]
+pub struct GaussianFilter {
+    pub radius: f32,
+    pub alpha: f32,
+}
+
+impl GaussianFilter {
+    pub fn new(radius: f32, alpha: f32) -> Filter {
+        Filter::Gaussian(GaussianFilter { radius, alpha })
+    }
+
+    fn gaussian(&self, d: f32) -> f32 {
+        ((-self.alpha * d * d).exp() - (-self.alpha * self.radius * self.radius).exp()).max(0.0)
+    }
+}
+
+impl Filterable for GaussianFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+// Mitchell-Netravali, b = c = 1/3 is the recommended balance of blur and ringing
+#[derive(Copy, Clone, Debug)]
+pub struct MitchellFilter {
+    pub radius: f32,
+    pub b: f32,
+    pub c: f32,
+}
+
+impl MitchellFilter {
+    pub fn new(radius: f32, b: f32, c: f32) -> Filter {
+        Filter::Mitchell(MitchellFilter { radius, b, c })
+    }
+
+    fn mitchell(&self, x: f32) -> f32 {
+        let x = (2.0 * x / self.radius).abs();
+        let (b, c) = (self.b, self.c);
+        if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c))
+                / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b))
+                / 6.0
+        }
+    }
+}
+
+impl Filterable for MitchellFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+// sinc windowed by a wider sinc, `lobes` is the number of sinc lobes inside the radius
+#[derive(Copy, Clone, Debug)]
+pub struct LanczosFilter {
+    pub radius: f32,
+    pub lobes: f32,
+}
+
+impl LanczosFilter {
+    pub fn new(radius: f32, lobes: f32) -> Filter {
+        Filter::Lanczos(LanczosFilter { radius, lobes })
+    }
+
+    fn lanczos(&self, x: f32) -> f32 {
+        let x = x.abs() / self.radius;
+        if x >= 1.0 {
+            return 0.0;
+        }
+        sinc(x * self.lobes) * sinc(x)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+impl Filterable for LanczosFilter {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn evaluate(&self, x: f32, y: f32) -> f32 {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
@@ -0,0 +1,352 @@
+use image::DynamicImage;
+
+use crate::aabb::Aabb;
+use crate::intersection::Intersection;
+use crate::materials::Material;
+use crate::objects::{Intersectable, Object};
+use crate::perlin::Perlin;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+
+// cells per side of a leaf before the tree stops splitting
+const LEAF_CELLS: usize = 4;
+
+#[derive(Clone, Debug)]
+struct HeightfieldNode {
+    i0: usize,
+    i1: usize,
+    j0: usize,
+    j1: usize,
+    bbox: Aabb,
+    children: Option<(usize, usize)>,
+}
+
+// samples and noise of a heightfield made from Perlin noise, see `Perlin::grid`
+#[derive(Copy, Clone, Debug)]
+pub struct PerlinGrid {
+    pub nx: usize,
+    pub nz: usize,
+    pub frequency: f32,
+    pub octaves: u32,
+}
+
+// Regular grid of heights intersected directly. The cells are kept in a min/max
+// kd-tree so a ray only tests the triangles of the cells it can actually reach.
+#[derive(Clone, Debug)]
+pub struct Heightfield {
+    nx: usize,
+    nz: usize,
+    heights: Vec<f32>,
+    normals: Vec<Vec3>,
+    origin: Vec3,
+    size: Vec3,
+    nodes: Vec<HeightfieldNode>,
+    material: Material,
+}
+
+impl Heightfield {
+    // `heights` holds nx * nz samples in [0, 1], row by row along x. the grid spans
+    // `size.x` by `size.z` from `origin` and is `size.y` tall
+    pub fn new(
+        heights: Vec<f32>,
+        nx: usize,
+        nz: usize,
+        origin: Vec3,
+        size: Vec3,
+        material: Material,
+    ) -> Object {
+        assert!(nx >= 2 && nz >= 2, "heightfield needs at least 2x2 samples");
+        assert_eq!(heights.len(), nx * nz, "heightfield sample count mismatch");
+
+        let mut field = Heightfield {
+            nx,
+            nz,
+            heights,
+            normals: Vec::new(),
+            origin,
+            size,
+            nodes: Vec::new(),
+            material,
+        };
+
+        field.normals = (0..nz)
+            .flat_map(|j| (0..nx).map(move |i| (i, j)))
+            .map(|(i, j)| field.vertex_normal(i, j))
+            .collect();
+        field.build(0, nx - 1, 0, nz - 1);
+
+        Object::Heightfield(field)
+    }
+
+    pub fn from_image(
+        image: &DynamicImage,
+        origin: Vec3,
+        size: Vec3,
+        material: Material,
+    ) -> Object {
+        let gray = image.to_luma16();
+        let (nx, nz) = (gray.width() as usize, gray.height() as usize);
+        let heights = gray
+            .pixels()
+            .map(|p| p[0] as f32 / u16::MAX as f32)
+            .collect();
+
+        Heightfield::new(heights, nx, nz, origin, size, material)
+    }
+
+    pub fn from_perlin(
+        perlin: &Perlin,
+        grid: &PerlinGrid,
+        origin: Vec3,
+        size: Vec3,
+        material: Material,
+    ) -> Object {
+        let heights = perlin.grid(grid.nx, grid.nz, grid.frequency, grid.octaves);
+        Heightfield::new(heights, grid.nx, grid.nz, origin, size, material)
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Vec3 {
+        Vec3::new(
+            self.origin.x + i as f32 / (self.nx - 1) as f32 * self.size.x,
+            self.origin.y + self.heights[j * self.nx + i] * self.size.y,
+            self.origin.z + j as f32 / (self.nz - 1) as f32 * self.size.z,
+        )
+    }
+
+    fn vertex_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (il, ir) = (i.saturating_sub(1), (i + 1).min(self.nx - 1));
+        let (jl, jr) = (j.saturating_sub(1), (j + 1).min(self.nz - 1));
+
+        let dx = self.vertex(ir, j) - self.vertex(il, j);
+        let dz = self.vertex(i, jr) - self.vertex(i, jl);
+        dz.cross(&dx).normalize()
+    }
+
+    fn build(&mut self, i0: usize, i1: usize, j0: usize, j1: usize) -> usize {
+        let mut min_y = f32::MAX;
+        let mut max_y = f32::MIN;
+        for j in j0..=j1 {
+            for i in i0..=i1 {
+                let y = self.vertex(i, j).y;
+                min_y = min_y.min(y);
+                max_y = max_y.max(y);
+            }
+        }
+
+        let low = self.vertex(i0, j0);
+        let high = self.vertex(i1, j1);
+        let index = self.nodes.len();
+        self.nodes.push(HeightfieldNode {
+            i0,
+            i1,
+            j0,
+            j1,
+            bbox: Aabb {
+                min: Vec3::new(low.x, min_y - 1e-4, low.z),
+                max: Vec3::new(high.x, max_y + 1e-4, high.z),
+            },
+            children: None,
+        });
+
+        let children = if i1 - i0 <= LEAF_CELLS && j1 - j0 <= LEAF_CELLS {
+            None
+        } else if i1 - i0 >= j1 - j0 {
+            let mid = (i0 + i1) / 2;
+            Some((self.build(i0, mid, j0, j1), self.build(mid, i1, j0, j1)))
+        } else {
+            let mid = (j0 + j1) / 2;
+            Some((self.build(i0, i1, j0, mid), self.build(i0, i1, mid, j1)))
+        };
+        self.nodes[index].children = children;
+
+        index
+    }
+
+    // Möller-Trumbore, returns the distance and barycentrics of v1 and v2
+    fn triangle(ray: &Ray, v0: Vec3, v1: Vec3, v2: Vec3) -> Option<(f32, f32, f32)> {
+        let v0v1 = v1 - v0;
+        let v0v2 = v2 - v0;
+        let pvec = ray.direction.cross(&v0v2);
+        let det = v0v1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let inv_det = 1.0 / det;
+
+        let tvec = ray.origin - v0;
+        let u = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let qvec = tvec.cross(&v0v1);
+        let v = ray.direction.dot(&qvec) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        Some((v0v2.dot(&qvec) * inv_det, u, v))
+    }
+
+    // distance to the closest hit inside one cell
+    fn intersect_cell(&self, ray: &Ray, i: usize, j: usize, t_min: f32, t_max: f32) -> Option<f32> {
+        let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+        let mut closest: Option<f32> = None;
+        let mut t_max = t_max;
+
+        for tri in [[0, 1, 2], [0, 2, 3]].iter() {
+            let idx = [corners[tri[0]], corners[tri[1]], corners[tri[2]]];
+            let v = [
+                self.vertex(idx[0].0, idx[0].1),
+                self.vertex(idx[1].0, idx[1].1),
+                self.vertex(idx[2].0, idx[2].1),
+            ];
+
+            if let Some((t, _, _)) = Heightfield::triangle(ray, v[0], v[1], v[2]) {
+                if t >= t_min && t <= t_max {
+                    t_max = t;
+                    closest = Some(t);
+                }
+            }
+        }
+
+        closest
+    }
+
+    // cell under a point on the grid and where in the cell it is
+    fn cell_at(&self, point: &Vec3) -> (usize, usize, f32, f32) {
+        let fx = ((point.x - self.origin.x) / self.size.x * (self.nx - 1) as f32)
+            .max(0.0)
+            .min((self.nx - 1) as f32);
+        let fz = ((point.z - self.origin.z) / self.size.z * (self.nz - 1) as f32)
+            .max(0.0)
+            .min((self.nz - 1) as f32);
+        let i = (fx as usize).min(self.nx - 2);
+        let j = (fz as usize).min(self.nz - 2);
+        (i, j, fx - i as f32, fz - j as f32)
+    }
+
+    // bilinear blend of the vertex normals around a point on the grid
+    fn normal_at(&self, point: &Vec3) -> Vec3 {
+        let (i, j, s, t) = self.cell_at(point);
+        let n = |ni: usize, nj: usize| self.normals[nj * self.nx + ni];
+        (n(i, j) * ((1.0 - s) * (1.0 - t))
+            + n(i + 1, j) * (s * (1.0 - t))
+            + n(i, j + 1) * ((1.0 - s) * t)
+            + n(i + 1, j + 1) * (s * t))
+            .normalize()
+    }
+
+    // derivatives of a point on the grid along u and v, with the slope of the
+    // bilinear heights of its cell
+    fn tangents_at(&self, point: &Vec3) -> (Vec3, Vec3) {
+        let (i, j, s, t) = self.cell_at(point);
+        let h = |hi: usize, hj: usize| self.heights[hj * self.nx + hi];
+        let dhds = (h(i + 1, j) - h(i, j)) * (1.0 - t) + (h(i + 1, j + 1) - h(i, j + 1)) * t;
+        let dhdt = (h(i, j + 1) - h(i, j)) * (1.0 - s) + (h(i + 1, j + 1) - h(i + 1, j)) * s;
+
+        // u and v span nx - 1 and nz - 1 cells
+        (
+            Vec3::new(self.size.x, dhds * (self.nx - 1) as f32 * self.size.y, 0.0),
+            Vec3::new(0.0, dhdt * (self.nz - 1) as f32 * self.size.y, self.size.z),
+        )
+    }
+}
+
+impl Intersectable for Heightfield {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        let mut closest: Option<f32> = None;
+        let mut t_max = t_max;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node.bbox.hit(ray, t_min, t_max) {
+                continue;
+            }
+
+            match node.children {
+                Some((a, b)) => {
+                    // visit the child nearer to the ray origin first
+                    let split_on_x = self.nodes[a].i1 != node.i1;
+                    let reversed = if split_on_x {
+                        ray.direction.x < 0.0
+                    } else {
+                        ray.direction.z < 0.0
+                    };
+                    if reversed {
+                        stack.push(a);
+                        stack.push(b);
+                    } else {
+                        stack.push(b);
+                        stack.push(a);
+                    }
+                }
+                None => {
+                    for j in node.j0..node.j1 {
+                        for i in node.i0..node.i1 {
+                            if let Some(t) = self.intersect_cell(ray, i, j, t_min, t_max) {
+                                t_max = t;
+                                closest = Some(t);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let distance = closest?;
+        let point = ray.at(distance);
+        // the same bilinear normal `surface_normal` gives
+        let outward_normal = self.outward_normal(&point, ray.time);
+        let (dpdu, dpdv) = self.tangents_at(&point);
+
+        Some(
+            Intersection::new(
+                distance,
+                point,
+                if ray.front_face(&outward_normal) {
+                    outward_normal
+                } else {
+                    -outward_normal
+                },
+                outward_normal,
+                &self.material,
+                self.surface_uv(&point),
+            )
+            .with_tangents(dpdu, dpdv),
+        )
+    }
+
+    fn surface_normal(&self, point: &Vec3, ray: &Ray) -> Vec3 {
+        let normal = self.normal_at(point);
+        if ray.front_face(&normal) {
+            normal
+        } else {
+            -normal
+        }
+    }
+
+    fn outward_normal(&self, point: &Vec3, _time: f32) -> Vec3 {
+        self.normal_at(point)
+    }
+
+    // spans the whole grid once
+    fn surface_uv(&self, point: &Vec3) -> (f32, f32) {
+        (
+            (point.x - self.origin.x) / self.size.x,
+            (point.z - self.origin.z) / self.size.z,
+        )
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.nodes[0].bbox)
+    }
+}
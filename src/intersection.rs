@@ -1,4 +1,6 @@
-use crate::{materials::Material, objects::Object, ray::Ray, vec3::Vec3};
+use crate::{
+    materials::Material, microfacet::Onb, objects::Object, ray::Ray, sampler::Sampler, vec3::Vec3,
+};
 
 #[derive(Clone, Debug, Copy)]
 pub struct Intersection<'trace> {
@@ -8,6 +10,12 @@ pub struct Intersection<'trace> {
     pub outward_normal: Vec3,
     pub mat: &'trace Material,
     pub uv: (f32, f32),
+    // index of the top level object in the `World` that was hit
+    pub object_id: u32,
+    // derivatives of the point along u and v, zero when the primitive has no uv
+    // parameterization
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
 }
 
 impl<'trace> Intersection<'trace> {
@@ -26,6 +34,71 @@ impl<'trace> Intersection<'trace> {
             outward_normal,
             mat: &mat,
             uv,
+            object_id: 0,
+            dpdu: Vec3::zero(),
+            dpdv: Vec3::zero(),
         }
     }
+
+    pub fn with_tangents(mut self, dpdu: Vec3, dpdv: Vec3) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    // Unit tangent and bitangent around the shading normal, following dpdu when
+    // there is one and any perpendicular pair otherwise.
+    pub fn shading_frame(&self) -> (Vec3, Vec3) {
+        let n = self.normal;
+        let t = self.dpdu - n * n.dot(&self.dpdu);
+        if t.norm() > 1e-12 {
+            let t = t.normalize();
+            let b = n.cross(&t);
+            // keep the bitangent along dpdv so normal maps are not mirrored
+            if b.dot(&self.dpdv) < 0.0 {
+                (t, -b)
+            } else {
+                (t, b)
+            }
+        } else {
+            let onb = Onb::new(n);
+            (onb.u, onb.v)
+        }
+    }
+
+    // Whether the hit survives the opacity of its material, fractional opacity
+    // lets rays through at random
+    pub fn alpha_test(&self, sampler: &mut dyn Sampler) -> bool {
+        let opacity = self.mat.opacity(self.uv, self.point);
+        if opacity >= 1.0 {
+            return true;
+        }
+        if opacity <= 0.0 {
+            return false;
+        }
+        sampler.next_1d() < opacity
+    }
+
+    // Replaces the shading normal, keeping both normals on the sides they were on
+    pub fn with_normal(mut self, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        self.normal = if normal.dot(&self.normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        self.outward_normal = if normal.dot(&self.outward_normal) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        self
+    }
+}
+
+// span of a ray that lies inside a closed object
+#[derive(Clone, Debug, Copy)]
+pub struct Interval<'trace> {
+    pub enter: Intersection<'trace>,
+    pub exit: Intersection<'trace>,
 }
@@ -0,0 +1,134 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use image::DynamicImage;
+
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+
+// Shape of the lens opening, which is also the shape of out of focus highlights.
+// Samples are points on the lens in [-1, 1] x [-1, 1], scaled by the lens radius.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum Aperture {
+    Circle,
+    // regular polygon from straight aperture blades, rotation in degrees
+    Polygon { blades: u32, rotation: f32 },
+    Mask(ApertureMask),
+}
+
+impl Aperture {
+    pub fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        match self {
+            Aperture::Circle => sampler.in_unit_disk(),
+            Aperture::Polygon { blades, rotation } => {
+                let blades = (*blades).max(3);
+                let step = 2.0 * PI / blades as f32;
+                let blade = ((sampler.next_1d() * blades as f32) as u32).min(blades - 1);
+                let a0 = rotation.to_radians() + blade as f32 * step;
+                let v0 = Vec3::new(a0.cos(), a0.sin(), 0.0);
+                let v1 = Vec3::new((a0 + step).cos(), (a0 + step).sin(), 0.0);
+
+                // uniform point in the triangle between the center and one edge
+                let (mut r1, mut r2) = sampler.next_2d();
+                if r1 + r2 > 1.0 {
+                    r1 = 1.0 - r1;
+                    r2 = 1.0 - r2;
+                }
+                r1 * v0 + r2 * v1
+            }
+            Aperture::Mask(mask) => mask.sample(sampler),
+        }
+    }
+}
+
+// Aperture shape read from an image, brighter pixels let more light through.
+#[derive(Clone, Debug)]
+pub struct ApertureMask {
+    width: u32,
+    height: u32,
+    cdf: Arc<Vec<f32>>,
+}
+
+impl ApertureMask {
+    pub fn new(img: &DynamicImage) -> Aperture {
+        let gray = img.to_luma8();
+        let (width, height) = gray.dimensions();
+
+        let mut total = 0.0;
+        let mut cdf: Vec<f32> = gray
+            .pixels()
+            .map(|p| {
+                total += p[0] as f32 / 255.0;
+                total
+            })
+            .collect();
+        assert!(total > 0.0, "aperture mask is completely black");
+        cdf.iter_mut().for_each(|c| *c /= total);
+
+        Aperture::Mask(ApertureMask {
+            width,
+            height,
+            cdf: Arc::new(cdf),
+        })
+    }
+
+    fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let r = sampler.next_1d();
+        let index = self.cdf.partition_point(|c| *c < r).min(self.cdf.len() - 1);
+        let (jx, jy) = sampler.next_2d();
+        let x = (index as u32 % self.width) as f32 + jx;
+        let y = (index as u32 / self.width) as f32 + jy;
+
+        Vec3::new(
+            2.0 * x / self.width as f32 - 1.0,
+            1.0 - 2.0 * y / self.height as f32,
+            0.0,
+        )
+    }
+}
+
+// Real camera settings, lengths in millimeters. Scene units are taken to be meters.
+#[derive(Copy, Clone, Debug)]
+pub struct PhysicalLens {
+    pub sensor_width: f32,
+    pub sensor_height: f32,
+    pub focal_length: f32,
+    pub f_number: f32,
+}
+
+impl PhysicalLens {
+    pub fn new(
+        sensor_width: f32,
+        sensor_height: f32,
+        focal_length: f32,
+        f_number: f32,
+    ) -> PhysicalLens {
+        PhysicalLens {
+            sensor_width,
+            sensor_height,
+            focal_length,
+            f_number,
+        }
+    }
+
+    // 36 x 24mm full frame sensor
+    pub fn full_frame(focal_length: f32, f_number: f32) -> PhysicalLens {
+        PhysicalLens::new(36.0, 24.0, focal_length, f_number)
+    }
+
+    pub fn vfov(&self) -> f32 {
+        2.0 * (self.sensor_height / (2.0 * self.focal_length))
+            .atan()
+            .to_degrees()
+    }
+
+    pub fn aspect_ratio(&self) -> f32 {
+        self.sensor_width / self.sensor_height
+    }
+
+    // diameter of the entrance pupil in scene units
+    pub fn aperture(&self) -> f32 {
+        self.focal_length / self.f_number / 1000.0
+    }
+}
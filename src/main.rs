@@ -5,19 +5,39 @@ use std::sync::Arc;
 
 use image::io::Reader as ImageReader;
 use image::{DynamicImage, ImageBuffer, RgbImage};
+pub mod animation;
 pub mod color;
+pub mod cryptomatte;
 pub mod intersection;
 pub mod materials;
 pub mod objects;
 pub mod ray;
 pub mod rendering;
+pub mod sampler;
+pub mod stereo;
 
 pub mod aabb;
+pub mod adaptive;
+pub mod aov;
 pub mod bvh;
 pub mod bvh2;
+pub mod exr;
+pub mod film;
+pub mod filter;
+pub mod heightfield;
+pub mod lens;
+pub mod microfacet;
 pub mod perlin;
+pub mod progressive;
+pub mod projection;
+pub mod sdf;
+pub mod spectrum;
 pub mod texture;
+pub mod thinfilm;
+pub mod tiles;
+pub mod transform;
 pub mod vec3;
+pub mod world;
 use color::*;
 
 use materials::{Dielectric, EmissiveDiffuse, Isotropic, Lambertian, Metal};
@@ -132,10 +152,11 @@ fn main() {
         clean_aux: false,
     });
 
-    let boxed_world = world.into_iter().map(|o| Box::new(o)).collect();
     // let bvh_world = BvhTree::new(&mut world);
-    let bvh_world = bvh2::BVH::new(boxed_world, 0.0, 1.0);
-    let img = camera.bvh2_render(&bvh_world, &color!(0.0, 0.0, 0.0), 800, 128, 50, None);
+    let bvh_world = world::World::new(world, 0.0, 1.0);
+    let img = camera
+        .bvh2_render(&bvh_world, &color!(0.0, 0.0, 0.0), 800, 128, 50, None)
+        .unwrap();
     img.save("test.png").unwrap();
     // let img = camera.threaded_render(50, &world, &color!(0.0, 0.0, 0.0), 800, 32, 3, None);
     // let img = camera.pog_render(&world, &color!(0.0, 0.0, 0.0), 1000, 128, 50, None);
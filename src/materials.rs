@@ -1,13 +1,23 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
 use crate::color::{Color, BLACK, WHITE};
 use crate::intersection::Intersection;
+use crate::microfacet::{
+    dielectric_eval, dielectric_pdf, fresnel_conductor, fresnel_dielectric, reflect,
+    reflection_eval, reflection_pdf, sample_dielectric, Ggx, Onb,
+};
 // use crate::objects::Object;
 use crate::ray::Ray;
-use crate::rendering::{random_distribution, random_sphere_distribution};
-use crate::texture::Texture;
+use crate::sampler::Sampler;
+use crate::spectrum::{sample_wavelength, wavelength_to_rgb, Dispersion, LAMBDA_D};
+use crate::texture::{SolidColor, Texture};
+use crate::thinfilm::{conductor_from_albedo, ThinFilm};
 use crate::vec3::Vec3;
 
+// Not `Copy` since `Mix`, `Bumped` and `Masked` own their children.
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum Material {
     Labertian(Lambertian),
     Metal(Metal),
@@ -15,23 +25,57 @@ pub enum Material {
     EmissiveDiffuse(EmissiveDiffuse),
     Isotropic(Isotropic),
     Glossy(Glossy),
+    Conductor(Conductor),
+    RoughDielectric(RoughDielectric),
+    Principled(Principled),
+    Mix(Mix),
+    Bumped(Bumped),
+    Masked(Masked),
 }
 
 pub trait Tracable {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<(Color, Ray)>;
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)>;
     fn emitted(&self, uv: (f32, f32), inter: &Intersection) -> Color;
     fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color;
+
+    // BSDF times the cosine at `wi` for light arriving along `wi` and leaving along
+    // `wo`, both unit vectors pointing away from the surface. Materials that only
+    // scatter into single directions leave this at zero.
+    fn eval(&self, _wo: Vec3, _wi: Vec3, _inter: &Intersection) -> Color {
+        BLACK
+    }
+
+    // density, per solid angle, of `scatter` picking `wi` when leaving along `wo`
+    fn pdf(&self, _wo: Vec3, _wi: Vec3, _inter: &Intersection) -> f32 {
+        0.0
+    }
 }
 
 impl Tracable for Material {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
         match *self {
-            Material::Labertian(ref mat) => mat.scatter(ray, inter),
-            Material::Metal(ref mat) => mat.scatter(ray, inter),
-            Material::Dielectric(ref mat) => mat.scatter(ray, inter),
-            Material::EmissiveDiffuse(ref mat) => mat.scatter(ray, inter),
-            Material::Isotropic(ref mat) => mat.scatter(ray, inter),
-            Material::Glossy(ref mat) => mat.scatter(ray, inter),
+            Material::Labertian(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Metal(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Dielectric(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::EmissiveDiffuse(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Isotropic(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Glossy(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Conductor(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::RoughDielectric(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Principled(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Mix(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Bumped(ref mat) => mat.scatter(ray, inter, sampler),
+            Material::Masked(ref mat) => mat.scatter(ray, inter, sampler),
         }
     }
 
@@ -43,6 +87,12 @@ impl Tracable for Material {
             Material::EmissiveDiffuse(ref mat) => mat.emitted(uv, inter),
             Material::Isotropic(ref _mat) => BLACK, //mat.emitted(uv, inter),
             Material::Glossy(ref _mat) => BLACK,
+            Material::Conductor(ref _mat) => BLACK,
+            Material::RoughDielectric(ref _mat) => BLACK,
+            Material::Principled(ref _mat) => BLACK,
+            Material::Mix(ref mat) => mat.emitted(uv, inter),
+            Material::Bumped(ref mat) => mat.emitted(uv, inter),
+            Material::Masked(ref mat) => mat.emitted(uv, inter),
         }
     }
 
@@ -54,12 +104,62 @@ impl Tracable for Material {
             Material::EmissiveDiffuse(ref mat) => mat.albedo(uv, point),
             Material::Isotropic(ref mat) => mat.albedo(uv, point),
             Material::Glossy(ref mat) => mat.albedo(uv, point),
+            Material::Conductor(ref mat) => mat.albedo(uv, point),
+            Material::RoughDielectric(ref mat) => mat.albedo(uv, point),
+            Material::Principled(ref mat) => mat.albedo(uv, point),
+            Material::Mix(ref mat) => mat.albedo(uv, point),
+            Material::Bumped(ref mat) => mat.albedo(uv, point),
+            Material::Masked(ref mat) => mat.albedo(uv, point),
+        }
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        match *self {
+            Material::Labertian(ref mat) => mat.eval(wo, wi, inter),
+            Material::Glossy(ref mat) => mat.eval(wo, wi, inter),
+            Material::Conductor(ref mat) => mat.eval(wo, wi, inter),
+            Material::RoughDielectric(ref mat) => mat.eval(wo, wi, inter),
+            Material::Principled(ref mat) => mat.eval(wo, wi, inter),
+            Material::Mix(ref mat) => mat.eval(wo, wi, inter),
+            Material::Bumped(ref mat) => mat.eval(wo, wi, inter),
+            Material::Masked(ref mat) => mat.eval(wo, wi, inter),
+            _ => BLACK,
+        }
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        match *self {
+            Material::Labertian(ref mat) => mat.pdf(wo, wi, inter),
+            Material::Glossy(ref mat) => mat.pdf(wo, wi, inter),
+            Material::Conductor(ref mat) => mat.pdf(wo, wi, inter),
+            Material::RoughDielectric(ref mat) => mat.pdf(wo, wi, inter),
+            Material::Principled(ref mat) => mat.pdf(wo, wi, inter),
+            Material::Mix(ref mat) => mat.pdf(wo, wi, inter),
+            Material::Bumped(ref mat) => mat.pdf(wo, wi, inter),
+            Material::Masked(ref mat) => mat.pdf(wo, wi, inter),
+            _ => 0.0,
+        }
+    }
+}
+
+impl Material {
+    // Chance of a ray stopping at the surface instead of passing through, see
+    // `Intersection::alpha_test`.
+    pub fn opacity(&self, uv: (f32, f32), point: Vec3) -> f32 {
+        match *self {
+            Material::Masked(ref mat) => mat.opacity(uv, point),
+            Material::Bumped(ref mat) => mat.material.opacity(uv, point),
+            Material::Mix(ref mat) => {
+                let t = mat.amount(uv, point);
+                mat.a.opacity(uv, point) * (1.0 - t) + mat.b.opacity(uv, point) * t
+            }
+            _ => 1.0,
         }
     }
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Lambertian {
     pub texture: Texture,
 }
@@ -71,9 +171,14 @@ impl Lambertian {
 }
 
 impl Tracable for Lambertian {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
         let normal = inter.normal;
-        let mut scatter_dir = inter.point + normal + random_sphere_distribution().normalize();
+        let mut scatter_dir = inter.point + normal + sampler.unit_vector();
         // let outward_normal = inter.outward_normal;
         let uv = inter.uv;
 
@@ -94,18 +199,49 @@ impl Tracable for Lambertian {
     fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
         self.texture.get_color_uv(uv, point)
     }
+
+    // `scatter` samples the cosine weighted hemisphere
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        let normal = inter.normal.normalize();
+        if wo.dot(&normal) * wi.dot(&normal) <= 0.0 {
+            return BLACK;
+        }
+        self.albedo(inter.uv, inter.point) * (wi.dot(&normal).abs() / PI)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        let normal = inter.normal.normalize();
+        if wo.dot(&normal) * wi.dot(&normal) <= 0.0 {
+            return 0.0;
+        }
+        wi.dot(&normal).abs() / PI
+    }
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Metal {
     pub texture: Texture,
     pub fuzz: f32,
+    // interference coating, like oil or an anodized layer
+    pub film: Option<ThinFilm>,
 }
 
 impl Metal {
     pub fn new(texture: Texture, fuzz: f32) -> Material {
-        Material::Metal(Metal { texture, fuzz })
+        Material::Metal(Metal {
+            texture,
+            fuzz,
+            film: None,
+        })
+    }
+
+    pub fn iridescent(texture: Texture, fuzz: f32, film: ThinFilm) -> Material {
+        Material::Metal(Metal {
+            texture,
+            fuzz,
+            film: Some(film),
+        })
     }
 
     pub fn reflect(v: Vec3, n: Vec3) -> Vec3 {
@@ -114,18 +250,34 @@ impl Metal {
 }
 
 impl Tracable for Metal {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
         let normal = inter.normal;
         let reflected = Metal::reflect(ray.direction.normalize(), normal);
         // let outward_normal = inter.outward_normal;
         let uv = inter.uv;
 
+        let albedo = self.texture.get_color_uv(uv, inter.point);
+        let attenuation = match self.film {
+            // the metal under the film is matched to the albedo
+            Some(film) => {
+                let (eta, k) = conductor_from_albedo(albedo);
+                let cos_theta = -ray.direction.normalize().dot(&normal);
+                film.reflectance(cos_theta, 1.0, eta, k, ray.wavelength)
+            }
+            None => albedo,
+        };
+
         if reflected.dot(&normal) > 0.0 {
             Some((
-                self.texture.get_color_uv(uv, inter.point),
+                attenuation,
                 Ray::new(
                     inter.point,
-                    reflected + self.fuzz * random_sphere_distribution().normalize(),
+                    reflected + self.fuzz * sampler.unit_vector(),
                     ray.time,
                 ),
             ))
@@ -147,15 +299,127 @@ impl Tracable for Metal {
 #[derive(Copy, Clone, Debug)]
 pub struct Dielectric {
     pub ir: f32,
+    // Beer-Lambert absorption coefficient per unit of distance inside
+    pub absorption: Color,
+    // replaces `ir` with an index that depends on the wavelength of the path
+    pub dispersion: Option<Dispersion>,
+    // interference coating on the surface
+    pub film: Option<ThinFilm>,
+    // rays go straight through instead of refracting, see `scatter_thin`
+    pub thin_walled: bool,
 }
 
 impl Dielectric {
     pub fn new(index_of_refraction: f32) -> Material {
         Material::Dielectric(Dielectric {
             ir: index_of_refraction,
+            absorption: BLACK,
+            dispersion: None,
+            film: None,
+            thin_walled: false,
+        })
+    }
+
+    // colored glass, light that travels `1 / density` inside is left with `color`
+    pub fn tinted(index_of_refraction: f32, color: Color, density: f32) -> Material {
+        Material::Dielectric(Dielectric {
+            ir: index_of_refraction,
+            absorption: Dielectric::absorption(color, density),
+            dispersion: None,
+            film: None,
+            thin_walled: false,
         })
     }
 
+    // Glass that splits white light, `color` and `density` tint it like `tinted`.
+    // Paths through it carry a single wavelength from then on, so it needs more
+    // samples to converge than clear glass.
+    pub fn dispersive(dispersion: Dispersion, color: Color, density: f32) -> Material {
+        Material::Dielectric(Dielectric {
+            ir: dispersion.ior(LAMBDA_D),
+            absorption: Dielectric::absorption(color, density),
+            dispersion: Some(dispersion),
+            film: None,
+            thin_walled: false,
+        })
+    }
+
+    // glass under a coating like oil or an anti reflective layer
+    pub fn iridescent(index_of_refraction: f32, film: ThinFilm) -> Material {
+        Material::Dielectric(Dielectric {
+            ir: index_of_refraction,
+            absorption: BLACK,
+            dispersion: None,
+            film: Some(film),
+            thin_walled: false,
+        })
+    }
+
+    // a window pane modelled as a single surface
+    pub fn thin_walled(index_of_refraction: f32) -> Material {
+        Material::Dielectric(Dielectric {
+            ir: index_of_refraction,
+            absorption: BLACK,
+            dispersion: None,
+            film: None,
+            thin_walled: true,
+        })
+    }
+
+    // a thin walled surface that is all film, with air on both sides
+    pub fn soap_bubble(film: ThinFilm) -> Material {
+        Material::Dielectric(Dielectric {
+            ir: film.ior,
+            absorption: BLACK,
+            dispersion: None,
+            film: Some(film),
+            thin_walled: true,
+        })
+    }
+
+    // Walls too thin to offset or bend the rays going through them. Without a
+    // film light bounces back and forth between the two faces of the wall, a
+    // film is taken to be the whole wall.
+    fn scatter_thin(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> (Color, Ray) {
+        let unit_direction = ray.direction.normalize();
+        let normal = if ray.front_face(&inter.outward_normal) {
+            inter.outward_normal
+        } else {
+            -inter.outward_normal
+        };
+        let cos_theta = (-unit_direction).dot(&normal).min(1.0);
+
+        let r = match self.film {
+            Some(film) => film.reflectance(cos_theta, 1.0, WHITE, BLACK, ray.wavelength),
+            None => {
+                let r = Dielectric::reflectance(cos_theta, 1.0 / self.ir);
+                let r = 2.0 * r / (1.0 + r);
+                Color::new(r, r, r)
+            }
+        };
+
+        let p = ((r.r + r.g + r.b) / 3.0).clamp(1e-4, 1.0 - 1e-4);
+        if sampler.next_1d() < p {
+            let reflected = Metal::reflect(unit_direction, normal);
+            (r / p, Ray::new(inter.point, reflected, ray.time))
+        } else {
+            (
+                (WHITE - r) / (1.0 - p),
+                Ray::new(inter.point, unit_direction, ray.time),
+            )
+        }
+    }
+
+    fn absorption(color: Color, density: f32) -> Color {
+        let sigma = |c: f32| -c.clamp(1e-6, 1.0).ln() * density;
+        Color::new(sigma(color.r), sigma(color.g), sigma(color.b))
+    }
+
     pub fn refract(uv: Vec3, normal: Vec3, etai_over_etat: f32) -> Vec3 {
         let cos_theta = (-uv).dot(&normal).min(1.0);
         let r_out_perp = etai_over_etat * (uv + cos_theta * normal);
@@ -171,19 +435,50 @@ impl Dielectric {
 }
 
 impl Tracable for Dielectric {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
         let outward_norm = inter.outward_normal;
         let normal;
 
-        let attenuation = WHITE;
+        if self.thin_walled {
+            return Some(self.scatter_thin(ray, inter, sampler));
+        }
+
+        let mut attenuation = WHITE;
         let refraction_r;
 
-        if ray.front_face(&outward_norm) {
-            refraction_r = 1.0 / self.ir;
+        // the first dispersive surface on a path picks its wavelength
+        let mut wavelength = ray.wavelength;
+        let ir = match self.dispersion {
+            Some(dispersion) => {
+                if wavelength == 0.0 {
+                    wavelength = sample_wavelength(sampler.next_1d());
+                    attenuation = wavelength_to_rgb(wavelength);
+                }
+                dispersion.ior(wavelength)
+            }
+            None => self.ir,
+        };
+
+        let front_face = ray.front_face(&outward_norm);
+        if front_face {
+            refraction_r = 1.0 / ir;
             normal = outward_norm;
         } else {
-            refraction_r = self.ir;
+            refraction_r = ir;
             normal = -outward_norm;
+            // the ray has been travelling inside since it entered
+            let distance = inter.distance * ray.direction.length();
+            attenuation = attenuation
+                * Color::new(
+                    (-self.absorption.r * distance).exp(),
+                    (-self.absorption.g * distance).exp(),
+                    (-self.absorption.b * distance).exp(),
+                );
         };
 
         let unit_direction = ray.direction.normalize();
@@ -191,14 +486,44 @@ impl Tracable for Dielectric {
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_r * sin_theta > 1.0;
-        let direction = if cannot_refract
-            || Dielectric::reflectance(cos_theta, refraction_r) > random_distribution()
-        {
+        let reflects = match self.film {
+            // interference tints reflected and refracted light differently, so one
+            // is picked by the average reflectance and weighted by its color
+            Some(film) => {
+                let (outside, inside) = if front_face { (1.0, ir) } else { (ir, 1.0) };
+                let r = film.reflectance(
+                    cos_theta,
+                    outside,
+                    Color::new(inside, inside, inside),
+                    BLACK,
+                    wavelength,
+                );
+                let p = ((r.r + r.g + r.b) / 3.0).clamp(1e-4, 1.0 - 1e-4);
+                if cannot_refract {
+                    attenuation = attenuation * r;
+                    true
+                } else if sampler.next_1d() < p {
+                    attenuation = attenuation * r / p;
+                    true
+                } else {
+                    attenuation = attenuation * (WHITE - r) / (1.0 - p);
+                    false
+                }
+            }
+            None => {
+                cannot_refract
+                    || Dielectric::reflectance(cos_theta, refraction_r) > sampler.next_1d()
+            }
+        };
+        let direction = if reflects {
             Metal::reflect(unit_direction, normal)
         } else {
             Dielectric::refract(unit_direction, normal, refraction_r)
         };
-        Some((attenuation, Ray::new(inter.point, direction, ray.time)))
+        Some((
+            attenuation,
+            Ray::new(inter.point, direction, ray.time).with_wavelength(wavelength),
+        ))
     }
 
     fn emitted(&self, _uv: (f32, f32), _inter: &Intersection) -> Color {
@@ -211,7 +536,7 @@ impl Tracable for Dielectric {
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct EmissiveDiffuse {
     texture: Texture,
 }
@@ -223,7 +548,12 @@ impl EmissiveDiffuse {
 }
 
 impl Tracable for EmissiveDiffuse {
-    fn scatter(&self, _ray: &Ray, _inter: &Intersection) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        _ray: &Ray,
+        _inter: &Intersection,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
         None
     }
 
@@ -239,7 +569,7 @@ impl Tracable for EmissiveDiffuse {
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Isotropic {
     texture: Texture,
 }
@@ -251,14 +581,15 @@ impl Isotropic {
 }
 
 impl Tracable for Isotropic {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<(Color, Ray)> {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
         Some((
             self.albedo(inter.uv, inter.point),
-            Ray::new(
-                inter.point,
-                random_sphere_distribution().normalize(),
-                ray.time,
-            ),
+            Ray::new(inter.point, sampler.unit_vector(), ray.time),
         ))
     }
 
@@ -271,46 +602,514 @@ impl Tracable for Isotropic {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+// Plastic and varnish: a diffuse base under a smooth or rough dielectric coat. Light
+// the coat does not reflect reaches the base, and light leaving the base loses what
+// the coat reflects back down. A white base under the coat reflects everything.
+#[derive(Clone, Debug)]
 pub struct Glossy {
     pub texture: Texture,
     pub roughness: f32,
+    // index of refraction of the coat
+    pub ior: f32,
 }
 
 impl Glossy {
     pub fn new(texture: Texture, roughness: f32) -> Material {
-        Material::Glossy(Glossy { texture, roughness })
+        Glossy::coated(texture, roughness, 1.5)
+    }
+
+    pub fn coated(texture: Texture, roughness: f32, ior: f32) -> Material {
+        Material::Glossy(Glossy {
+            texture,
+            roughness,
+            ior,
+        })
+    }
+
+    fn frame(wo: Vec3, inter: &Intersection) -> Onb {
+        let normal = inter.normal.normalize();
+        if wo.dot(&normal) < 0.0 {
+            Onb::new(-normal)
+        } else {
+            Onb::new(normal)
+        }
+    }
+
+    // Fraction of diffuse light the coat reflects back onto the base, from Egan and
+    // Hilgeman's fit to the hemispherical Fresnel reflectance.
+    fn internal_reflectance(&self) -> f32 {
+        let eta = self.ior;
+        -1.4399 / (eta * eta) + 0.7099 / eta + 0.6681 + 0.0636 * eta
+    }
+
+    // chance of sampling the coat instead of the base
+    fn coat_probability(&self, albedo: Color, wo: Vec3) -> f32 {
+        let f = fresnel_dielectric(wo.z, self.ior);
+        let base = (1.0 - f) * (albedo.r + albedo.g + albedo.b) / 3.0;
+        (f / (f + base).max(1e-6)).clamp(0.1, 0.9)
+    }
+
+    fn eval_local(&self, albedo: Color, wo: Vec3, wi: Vec3) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return BLACK;
+        }
+
+        let ggx = Ggx::from_roughness(self.roughness);
+        let m = (wo + wi).normalize();
+        let coat = fresnel_dielectric(wo.dot(&m), self.ior) * reflection_eval(&ggx, wo, wi);
+
+        let transmitted =
+            (1.0 - fresnel_dielectric(wo.z, self.ior)) * (1.0 - fresnel_dielectric(wi.z, self.ior));
+        let fdr = self.internal_reflectance();
+        let base = Color::new(
+            albedo.r / (1.0 - albedo.r * fdr),
+            albedo.g / (1.0 - albedo.g * fdr),
+            albedo.b / (1.0 - albedo.b * fdr),
+        ) * (transmitted * wi.z / (PI * self.ior * self.ior));
+
+        WHITE * coat + base
+    }
+
+    fn pdf_local(&self, albedo: Color, wo: Vec3, wi: Vec3) -> f32 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let ggx = Ggx::from_roughness(self.roughness);
+        let p = self.coat_probability(albedo, wo);
+        p * reflection_pdf(&ggx, wo, wi) + (1.0 - p) * wi.z / PI
     }
 }
 
-// not working :(
 impl Tracable for Glossy {
-    fn scatter(&self, ray: &Ray, inter: &Intersection) -> Option<(Color, Ray)> {
-        // let outward_normal = inter.outward_normal;
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let wo = -ray.direction.normalize();
+        let frame = Glossy::frame(wo, inter);
+        let wo = frame.to_local(wo);
+        let albedo = self.albedo(inter.uv, inter.point);
+
+        let wi = if sampler.next_1d() < self.coat_probability(albedo, wo) {
+            let (u1, u2) = sampler.next_2d();
+            reflect(
+                wo,
+                Ggx::from_roughness(self.roughness).sample_visible(wo, u1, u2),
+            )
+        } else {
+            sampler.cosine_hemisphere()
+        };
+
+        let pdf = self.pdf_local(albedo, wo, wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let weight = self.eval_local(albedo, wo, wi) / pdf;
+        Some((weight, Ray::new(inter.point, frame.to_world(wi), ray.time)))
+    }
+
+    fn emitted(&self, _uv: (f32, f32), _inter: &Intersection) -> Color {
+        BLACK
+    }
+
+    fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
+        self.texture.get_color_uv(uv, point)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        let frame = Glossy::frame(wo, inter);
+        let albedo = self.albedo(inter.uv, inter.point);
+        self.eval_local(albedo, frame.to_local(wo), frame.to_local(wi))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        let frame = Glossy::frame(wo, inter);
+        let albedo = self.albedo(inter.uv, inter.point);
+        self.pdf_local(albedo, frame.to_local(wo), frame.to_local(wi))
+    }
+}
+
+// Metal with a complex index of refraction `eta` + i`k` per color channel and GGX
+// microfacets.
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub struct Conductor {
+    pub eta: Color,
+    pub k: Color,
+    pub roughness: f32,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f32) -> Material {
+        Material::Conductor(Conductor { eta, k, roughness })
+    }
+
+    // indices for red, green and blue at roughly 650, 550 and 450nm
+    pub fn gold(roughness: f32) -> Material {
+        Conductor::new(
+            Color::new(0.18299, 0.42108, 1.3734),
+            Color::new(3.4242, 2.3459, 1.7704),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: f32) -> Material {
+        Conductor::new(
+            Color::new(0.27105, 0.67693, 1.3164),
+            Color::new(3.6092, 2.6248, 2.2921),
+            roughness,
+        )
+    }
+
+    pub fn aluminum(roughness: f32) -> Material {
+        Conductor::new(
+            Color::new(1.6574, 0.88037, 0.52123),
+            Color::new(9.2238, 6.2695, 4.837),
+            roughness,
+        )
+    }
+
+    // shading frame on the side `wo` is on
+    fn frame(wo: Vec3, inter: &Intersection) -> Onb {
+        let normal = inter.normal.normalize();
+        if wo.dot(&normal) < 0.0 {
+            Onb::new(-normal)
+        } else {
+            Onb::new(normal)
+        }
+    }
+}
+
+impl Tracable for Conductor {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let wo = -ray.direction.normalize();
+        let frame = Conductor::frame(wo, inter);
+        let ggx = Ggx::from_roughness(self.roughness);
+
+        let wo = frame.to_local(wo);
+        let (u1, u2) = sampler.next_2d();
+        let m = ggx.sample_visible(wo, u1, u2);
+        let wi = reflect(wo, m);
+        if wi.z <= 0.0 {
+            return None;
+        }
+
+        // eval / pdf, where D and most of G cancel out
+        let weight = fresnel_conductor(wo.dot(&m), self.eta, self.k) * (ggx.g(wo, wi) / ggx.g1(wo));
+        Some((weight, Ray::new(inter.point, frame.to_world(wi), ray.time)))
+    }
+
+    fn emitted(&self, _uv: (f32, f32), _inter: &Intersection) -> Color {
+        BLACK
+    }
+
+    fn albedo(&self, _uv: (f32, f32), _point: Vec3) -> Color {
+        fresnel_conductor(1.0, self.eta, self.k)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        let frame = Conductor::frame(wo, inter);
+        let (wo, wi) = (frame.to_local(wo), frame.to_local(wi));
+        let ggx = Ggx::from_roughness(self.roughness);
+        let m = (wo + wi).normalize();
+        fresnel_conductor(wo.dot(&m), self.eta, self.k) * reflection_eval(&ggx, wo, wi)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        let frame = Conductor::frame(wo, inter);
+        let ggx = Ggx::from_roughness(self.roughness);
+        reflection_pdf(&ggx, frame.to_local(wo), frame.to_local(wi))
+    }
+}
+
+// Glass with GGX microfacets, it reflects or refracts by the Fresnel term of the
+// sampled facet.
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub struct RoughDielectric {
+    pub ir: f32,
+    pub roughness: f32,
+}
+
+impl RoughDielectric {
+    pub fn new(index_of_refraction: f32, roughness: f32) -> Material {
+        Material::RoughDielectric(RoughDielectric {
+            ir: index_of_refraction,
+            roughness,
+        })
+    }
+
+    // shading frame on the side `wo` is on, with the index of refraction of the
+    // other side over this one
+    fn frame(&self, wo: Vec3, inter: &Intersection) -> (Onb, f32) {
+        let normal = inter.outward_normal.normalize();
+        if wo.dot(&normal) < 0.0 {
+            (Onb::new(-normal), 1.0 / self.ir)
+        } else {
+            (Onb::new(normal), self.ir)
+        }
+    }
+}
+
+impl Tracable for RoughDielectric {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let wo = -ray.direction.normalize();
+        let (frame, eta) = self.frame(wo, inter);
+        let ggx = Ggx::from_roughness(self.roughness);
+
+        let wo = frame.to_local(wo);
+        let u = sampler.next_2d();
+        let wi = sample_dielectric(&ggx, wo, eta, u, sampler.next_1d())?;
+
+        let weight = ggx.g(wo, wi) / ggx.g1(wo);
+        Some((
+            WHITE * weight,
+            Ray::new(inter.point, frame.to_world(wi), ray.time),
+        ))
+    }
+
+    fn emitted(&self, _uv: (f32, f32), _inter: &Intersection) -> Color {
+        BLACK
+    }
+
+    fn albedo(&self, _uv: (f32, f32), _point: Vec3) -> Color {
+        BLACK
+    }
 
-        let attenuation = self.texture.get_color_uv(inter.uv, inter.point);
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        let (frame, eta) = self.frame(wo, inter);
+        let ggx = Ggx::from_roughness(self.roughness);
+        WHITE * dielectric_eval(&ggx, frame.to_local(wo), frame.to_local(wi), eta)
+    }
 
-        // let mut scatter_dir = inter.point + inter.normal + random_sphere_distribution().normalize();
-        // if scatter_dir.near_zero() {
-        //     scatter_dir = inter.normal;
-        // }
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        let (frame, eta) = self.frame(wo, inter);
+        let ggx = Ggx::from_roughness(self.roughness);
+        dielectric_pdf(&ggx, frame.to_local(wo), frame.to_local(wi), eta)
+    }
+}
 
-        // let reflected = Metal::reflect(ray.direction.normalize(), inter.normal);
+// Disney style BSDF that layers a clearcoat over a mix of metal, glass and a
+// diffuse base with sheen. The textured parameters only use their red channel
+// except `base_color`.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Principled {
+    pub base_color: Texture,
+    pub metallic: Texture,
+    pub roughness: Texture,
+    // reflectance of dielectrics at normal incidence, 0.5 is 4%
+    pub specular: f32,
+    pub sheen: f32,
+    // how much the sheen takes on the base color
+    pub sheen_tint: f32,
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    pub transmission: f32,
+    pub ior: f32,
+}
 
-        // let combined = (scatter_dir + reflected / self.roughness).normalize();
-        Some((attenuation, Ray::new(inter.point, inter.normal, ray.time)))
-        // if random_distribution() > 0.5 {
-        //     // let direction = reflected + self.roughness * random_sphere_distribution().normalize();
+// lobe parameters looked up at one point
+struct PrincipledLobes {
+    base_color: Color,
+    metallic: f32,
+    spec: Ggx,
+    coat: Ggx,
+    // chance of sampling diffuse, specular, glass and clearcoat
+    weights: [f32; 4],
+}
 
-        //     Some((attenuation, Ray::new(inter.point, reflected, ray.time)))
-        // } else {
-        //     let normal = inter.normal;
+impl Principled {
+    // the glTF metallic-roughness model, every other lobe off
+    pub fn standard(base_color: Texture, metallic: f32, roughness: f32) -> Principled {
+        Principled {
+            base_color,
+            metallic: SolidColor::new(Color::new(metallic, metallic, metallic)),
+            roughness: SolidColor::new(Color::new(roughness, roughness, roughness)),
+            specular: 0.5,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.03,
+            transmission: 0.0,
+            ior: 1.5,
+        }
+    }
 
-        //     Some((
-        //         attenuation,
-        //         Ray::new(inter.point, scatter_dir - inter.point, ray.time),
-        //     ))
-        // }
+    pub fn new(base_color: Texture, metallic: f32, roughness: f32) -> Material {
+        Material::Principled(Principled::standard(base_color, metallic, roughness))
+    }
+
+    // Wavefront MTL with the PBR extension (Pr, Pm, Ps, Pc, Pcr), illumination
+    // models 6 and 7 are glass.
+    pub fn from_mtl(m: &tobj::Material) -> Material {
+        let param = |key: &str, default: f32| {
+            m.unknown_param
+                .get(key)
+                .and_then(|v| v.split_whitespace().next())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+
+        let base_color = SolidColor::new(Color::new(m.diffuse[0], m.diffuse[1], m.diffuse[2]));
+        let mut p = Principled::standard(base_color, param("Pm", 0.0), param("Pr", 0.5));
+        p.sheen = param("Ps", 0.0);
+        p.clearcoat = param("Pc", 0.0);
+        p.clearcoat_roughness = param("Pcr", p.clearcoat_roughness);
+        if m.optical_density > 0.0 {
+            p.ior = m.optical_density;
+        }
+        if let Some(6) | Some(7) = m.illumination_model {
+            p.transmission = 1.0;
+        }
+        Material::Principled(p)
+    }
+
+    fn lobes(&self, uv: (f32, f32), point: Vec3) -> PrincipledLobes {
+        let base_color = self.base_color.get_color_uv(uv, point);
+        let metallic = self.metallic.get_color_uv(uv, point).r.clamp(0.0, 1.0);
+        let roughness = self.roughness.get_color_uv(uv, point).r.clamp(0.0, 1.0);
+
+        let glass = (1.0 - metallic) * self.transmission;
+        let weights = [
+            (1.0 - metallic) * (1.0 - self.transmission),
+            1.0 - glass,
+            glass,
+            0.25 * self.clearcoat,
+        ];
+        let total: f32 = weights.iter().sum();
+
+        PrincipledLobes {
+            base_color,
+            metallic,
+            spec: Ggx::from_roughness(roughness),
+            coat: Ggx::from_roughness(self.clearcoat_roughness),
+            weights: weights.map(|w| w / total),
+        }
+    }
+
+    // shading frame on the side `wo` is on, with the index of refraction of the
+    // other side over this one
+    fn frame(&self, wo: Vec3, inter: &Intersection) -> (Onb, f32) {
+        let normal = inter.outward_normal.normalize();
+        if wo.dot(&normal) < 0.0 {
+            (Onb::new(-normal), 1.0 / self.ior)
+        } else {
+            (Onb::new(normal), self.ior)
+        }
+    }
+
+    // everything in local coordinates
+    fn eval_local(&self, lobes: &PrincipledLobes, wo: Vec3, wi: Vec3, eta: f32) -> Color {
+        let [diffuse, _, glass, _] = lobes.weights;
+        let mut f = BLACK;
+
+        if glass > 0.0 {
+            let value = dielectric_eval(&lobes.spec, wo, wi, eta);
+            // only what passes through takes on the base color
+            let tint = if wi.z < 0.0 { lobes.base_color } else { WHITE };
+            f = f + tint * (value * (1.0 - lobes.metallic) * self.transmission);
+        }
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return f;
+        }
+
+        let m = (wo + wi).normalize();
+        let cos_d = wi.dot(&m);
+
+        if diffuse > 0.0 {
+            // Burley's diffuse, darker at grazing angles on smooth surfaces
+            let fd90 = 0.5 + 2.0 * lobes.spec.alpha.sqrt() * cos_d * cos_d;
+            let fd = |cos: f32| 1.0 + (fd90 - 1.0) * (1.0 - cos).powi(5);
+            let d = lobes.base_color * (fd(wo.z) * fd(wi.z) * wi.z / PI);
+
+            let luminance = 0.2126 * lobes.base_color.r
+                + 0.7152 * lobes.base_color.g
+                + 0.0722 * lobes.base_color.b;
+            let tint = if luminance > 0.0 {
+                lobes.base_color / luminance
+            } else {
+                WHITE
+            };
+            let sheen_color = WHITE * (1.0 - self.sheen_tint) + tint * self.sheen_tint;
+            let sheen = sheen_color * (self.sheen * (1.0 - cos_d).powi(5) * wi.z);
+
+            let scale = (1.0 - lobes.metallic) * (1.0 - self.transmission);
+            f = f + (d + sheen) * scale;
+        }
+
+        // Schlick's Fresnel from the dielectric reflectance up to the base color for metals
+        let f0 = WHITE * (0.08 * self.specular * (1.0 - lobes.metallic))
+            + lobes.base_color * lobes.metallic;
+        let schlick = (1.0 - cos_d).powi(5);
+        let fresnel = f0 + (WHITE - f0) * schlick;
+        let opaque = 1.0 - (1.0 - lobes.metallic) * self.transmission;
+        f = f + fresnel * (reflection_eval(&lobes.spec, wo, wi) * opaque);
+
+        if self.clearcoat > 0.0 {
+            let fresnel = 0.04 + 0.96 * schlick;
+            f = f + WHITE
+                * (0.25 * self.clearcoat * fresnel * reflection_eval(&lobes.coat, wo, wi));
+        }
+        f
+    }
+
+    fn pdf_local(&self, lobes: &PrincipledLobes, wo: Vec3, wi: Vec3, eta: f32) -> f32 {
+        let [diffuse, spec, glass, coat] = lobes.weights;
+        let mut pdf = glass * dielectric_pdf(&lobes.spec, wo, wi, eta);
+        if wo.z > 0.0 && wi.z > 0.0 {
+            pdf += diffuse * wi.z / PI;
+            pdf += spec * reflection_pdf(&lobes.spec, wo, wi);
+            pdf += coat * reflection_pdf(&lobes.coat, wo, wi);
+        }
+        pdf
+    }
+}
+
+impl Tracable for Principled {
+    // picks one lobe to sample and weights by every lobe, so the lobes can overlap
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        let wo = -ray.direction.normalize();
+        let (frame, eta) = self.frame(wo, inter);
+        let lobes = self.lobes(inter.uv, inter.point);
+        let wo = frame.to_local(wo);
+
+        let [diffuse, spec, glass, _] = lobes.weights;
+        let choice = sampler.next_1d();
+        let u = sampler.next_2d();
+        let wi = if choice < diffuse {
+            sampler.cosine_hemisphere()
+        } else if choice < diffuse + spec {
+            reflect(wo, lobes.spec.sample_visible(wo, u.0, u.1))
+        } else if choice < diffuse + spec + glass {
+            sample_dielectric(&lobes.spec, wo, eta, u, sampler.next_1d())?
+        } else {
+            reflect(wo, lobes.coat.sample_visible(wo, u.0, u.1))
+        };
+
+        let pdf = self.pdf_local(&lobes, wo, wi, eta);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let weight = self.eval_local(&lobes, wo, wi, eta) / pdf;
+        Some((weight, Ray::new(inter.point, frame.to_world(wi), ray.time)))
     }
 
     fn emitted(&self, _uv: (f32, f32), _inter: &Intersection) -> Color {
@@ -318,7 +1117,238 @@ impl Tracable for Glossy {
     }
 
     fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
-        self.texture.get_color_uv(uv, point)
+        self.base_color.get_color_uv(uv, point)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        let (frame, eta) = self.frame(wo, inter);
+        let lobes = self.lobes(inter.uv, inter.point);
+        self.eval_local(&lobes, frame.to_local(wo), frame.to_local(wi), eta)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        let (frame, eta) = self.frame(wo, inter);
+        let lobes = self.lobes(inter.uv, inter.point);
+        self.pdf_local(&lobes, frame.to_local(wo), frame.to_local(wi), eta)
+    }
+}
+
+// Blends two materials by the red channel of `mask`, 0 is all `a` and 1 all `b`.
+// Mixes nest, so any number of materials can be layered. The children are shared
+// so copies of a mix, one per triangle of a mesh, stay cheap.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Mix {
+    pub a: Arc<Material>,
+    pub b: Arc<Material>,
+    pub mask: Texture,
+}
+
+impl Mix {
+    pub fn new(a: Material, b: Material, mask: Texture) -> Material {
+        Material::Mix(Mix {
+            a: Arc::new(a),
+            b: Arc::new(b),
+            mask,
+        })
+    }
+
+    fn amount(&self, uv: (f32, f32), point: Vec3) -> f32 {
+        self.mask.get_color_uv(uv, point).r.clamp(0.0, 1.0)
+    }
+}
+
+impl Tracable for Mix {
+    // picking a child by its share of the mix leaves its own weight unchanged
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        if sampler.next_1d() < self.amount(inter.uv, inter.point) {
+            self.b.scatter(ray, inter, sampler)
+        } else {
+            self.a.scatter(ray, inter, sampler)
+        }
+    }
+
+    fn emitted(&self, uv: (f32, f32), inter: &Intersection) -> Color {
+        let t = self.amount(uv, inter.point);
+        self.a.emitted(uv, inter) * (1.0 - t) + self.b.emitted(uv, inter) * t
+    }
+
+    fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
+        let t = self.amount(uv, point);
+        self.a.albedo(uv, point) * (1.0 - t) + self.b.albedo(uv, point) * t
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        let t = self.amount(inter.uv, inter.point);
+        self.a.eval(wo, wi, inter) * (1.0 - t) + self.b.eval(wo, wi, inter) * t
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        let t = self.amount(inter.uv, inter.point);
+        self.a.pdf(wo, wi, inter) * (1.0 - t) + self.b.pdf(wo, wi, inter) * t
+    }
+}
+
+// where the perturbed normal of a `Bumped` material comes from
+#[derive(Clone, Debug)]
+pub enum NormalSource {
+    // tangent space normals packed into [0, 1], blue along the surface normal
+    NormalMap(Texture),
+    // heights along the surface normal, read from the red channel
+    Height(Texture),
+}
+
+// Shades `material` with a normal perturbed by a normal or bump map. Needs the
+// tangent frame of the intersection, without one an arbitrary frame is used.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Bumped {
+    pub material: Arc<Material>,
+    pub source: NormalSource,
+    // scales the tangent part of a normal map, or the heights in world units
+    pub strength: f32,
+}
+
+impl Bumped {
+    pub fn normal_map(material: Material, map: Texture) -> Material {
+        Material::Bumped(Bumped {
+            material: Arc::new(material),
+            source: NormalSource::NormalMap(map),
+            strength: 1.0,
+        })
+    }
+
+    pub fn bump(material: Material, height: Texture, strength: f32) -> Material {
+        Material::Bumped(Bumped {
+            material: Arc::new(material),
+            source: NormalSource::Height(height),
+            strength,
+        })
+    }
+
+    fn shade<'a>(&self, inter: &Intersection<'a>) -> Intersection<'a> {
+        let n = inter.normal;
+        let normal = match self.source {
+            NormalSource::NormalMap(ref map) => {
+                let c = map.get_color_uv(inter.uv, inter.point);
+                let (t, b) = inter.shading_frame();
+                self.strength * ((2.0 * c.r - 1.0) * t + (2.0 * c.g - 1.0) * b)
+                    + (2.0 * c.b - 1.0) * n
+            }
+            NormalSource::Height(ref height) => {
+                // finite differences along dpdu and dpdv, or along a unit frame
+                // in world space for primitives without uv
+                let has_tangents = inter.dpdu.norm() > 0.0 && inter.dpdv.norm() > 0.0;
+                let (tu, tv, step, uv_step) = if has_tangents {
+                    (inter.dpdu, inter.dpdv, 5e-4, 5e-4)
+                } else {
+                    let (t, b) = inter.shading_frame();
+                    (t, b, 1e-3, 0.0)
+                };
+                let h = |du: f32, dv: f32| {
+                    let uv = (inter.uv.0 + du * uv_step, inter.uv.1 + dv * uv_step);
+                    let point = inter.point + (du * step) * tu + (dv * step) * tv;
+                    height.get_color_uv(uv, point).r
+                };
+
+                let h0 = h(0.0, 0.0);
+                let dhdu = self.strength * (h(1.0, 0.0) - h0) / step;
+                let dhdv = self.strength * (h(0.0, 1.0) - h0) / step;
+                // `with_normal` turns it to the side of `n` whatever the handedness
+                (tu + dhdu * n).cross(&(tv + dhdv * n))
+            }
+        };
+
+        if normal.norm() < 1e-12 {
+            *inter
+        } else {
+            inter.with_normal(normal)
+        }
+    }
+}
+
+impl Tracable for Bumped {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.material.scatter(ray, &self.shade(inter), sampler)
+    }
+
+    fn emitted(&self, uv: (f32, f32), inter: &Intersection) -> Color {
+        self.material.emitted(uv, &self.shade(inter))
+    }
+
+    fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
+        self.material.albedo(uv, point)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        self.material.eval(wo, wi, &self.shade(inter))
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        self.material.pdf(wo, wi, &self.shade(inter))
+    }
+}
+
+// Cuts `material` out where the red channel of `alpha` times `opacity` is below
+// one, used for foliage and fences modelled as textured quads. The cut is made
+// by the intersection routines, the material itself shades as usual.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Masked {
+    pub material: Arc<Material>,
+    pub alpha: Texture,
+    pub opacity: f32,
+}
+
+impl Masked {
+    pub fn new(material: Material, alpha: Texture, opacity: f32) -> Material {
+        Material::Masked(Masked {
+            material: Arc::new(material),
+            alpha,
+            opacity,
+        })
+    }
+
+    fn opacity(&self, uv: (f32, f32), point: Vec3) -> f32 {
+        let alpha = self.alpha.get_color_uv(uv, point).r * self.opacity;
+        alpha * self.material.opacity(uv, point)
+    }
+}
+
+impl Tracable for Masked {
+    fn scatter(
+        &self,
+        ray: &Ray,
+        inter: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Option<(Color, Ray)> {
+        self.material.scatter(ray, inter, sampler)
+    }
+
+    fn emitted(&self, uv: (f32, f32), inter: &Intersection) -> Color {
+        self.material.emitted(uv, inter)
+    }
+
+    fn albedo(&self, uv: (f32, f32), point: Vec3) -> Color {
+        self.material.albedo(uv, point)
+    }
+
+    fn eval(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> Color {
+        self.material.eval(wo, wi, inter)
+    }
+
+    fn pdf(&self, wo: Vec3, wi: Vec3, inter: &Intersection) -> f32 {
+        self.material.pdf(wo, wi, inter)
     }
 }
 
@@ -0,0 +1,264 @@
+use std::f32::consts::PI;
+
+use crate::color::Color;
+use crate::vec3::Vec3;
+
+// Orthonormal basis with `w` along a normal. Local coordinates have the normal as +z.
+#[derive(Copy, Clone, Debug)]
+pub struct Onb {
+    pub u: Vec3,
+    pub v: Vec3,
+    pub w: Vec3,
+}
+
+impl Onb {
+    // branchless construction from Duff et al. 2017, `n` must be normalized
+    pub fn new(n: Vec3) -> Onb {
+        let sign = 1.0f32.copysign(n.z);
+        let a = -1.0 / (sign + n.z);
+        let b = n.x * n.y * a;
+        Onb {
+            u: Vec3::new(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x),
+            v: Vec3::new(b, sign + n.y * n.y * a, -n.y),
+            w: n,
+        }
+    }
+
+    pub fn to_local(&self, a: Vec3) -> Vec3 {
+        Vec3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+
+    pub fn to_world(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
+
+// GGX / Trowbridge-Reitz normal distribution, every direction is in local coordinates
+#[derive(Copy, Clone, Debug)]
+pub struct Ggx {
+    pub alpha: f32,
+}
+
+impl Ggx {
+    // artists' roughness is squared, a tiny minimum keeps smooth surfaces finite
+    pub fn from_roughness(roughness: f32) -> Ggx {
+        Ggx {
+            alpha: (roughness * roughness).max(1e-4),
+        }
+    }
+
+    pub fn d(&self, m: Vec3) -> f32 {
+        if m.z <= 0.0 {
+            return 0.0;
+        }
+        let a2 = self.alpha * self.alpha;
+        let t = m.z * m.z * (a2 - 1.0) + 1.0;
+        a2 / (PI * t * t)
+    }
+
+    fn lambda(&self, w: Vec3) -> f32 {
+        let cos2 = w.z * w.z;
+        if cos2 == 0.0 {
+            return f32::INFINITY;
+        }
+        let tan2 = (1.0 - cos2).max(0.0) / cos2;
+        0.5 * (-1.0 + (1.0 + self.alpha * self.alpha * tan2).sqrt())
+    }
+
+    pub fn g1(&self, w: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // height correlated masking-shadowing
+    pub fn g(&self, wo: Vec3, wi: Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Samples a normal as seen from `wo` (Heitz 2018), so no samples are wasted on
+    // facets facing away from the viewer. `wo` must be above the surface.
+    pub fn sample_visible(&self, wo: Vec3, u1: f32, u2: f32) -> Vec3 {
+        let vh = Vec3::new(self.alpha * wo.x, self.alpha * wo.y, wo.z).normalize();
+
+        let len2 = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if len2 > 0.0 {
+            Vec3::new(-vh.y, vh.x, 0.0) / len2.sqrt()
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        Vec3::new(self.alpha * nh.x, self.alpha * nh.y, nh.z.max(1e-6)).normalize()
+    }
+
+    // density of `sample_visible` returning `m`
+    pub fn visible_pdf(&self, wo: Vec3, m: Vec3) -> f32 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(&m).max(0.0) * self.d(m) / wo.z
+    }
+}
+
+// mirror `w` about `m`, both pointing away from the surface
+pub fn reflect(w: Vec3, m: Vec3) -> Vec3 {
+    2.0 * w.dot(&m) * m - w
+}
+
+// Refracts `w` through a facet `m` on its side, `eta` is the inside over the outside
+// index of refraction. None on total internal reflection.
+pub fn refract(w: Vec3, m: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = w.dot(&m);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(-w / eta + (cos_i / eta - cos_t) * m)
+}
+
+// unpolarized Fresnel reflectance of a dielectric, `eta` as in `refract`
+pub fn fresnel_dielectric(cos_i: f32, eta: f32) -> f32 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// Fresnel reflectance of a conductor with complex index of refraction eta + ik
+pub fn fresnel_conductor(cos_i: f32, eta: Color, k: Color) -> Color {
+    let fresnel = |eta: f32, k: f32| {
+        let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let t0 = eta * eta - k * k - sin2;
+        let a2b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let a = (0.5 * (a2b2 + t0)).max(0.0).sqrt();
+
+        let t1 = a2b2 + cos2;
+        let t2 = 2.0 * cos_i.clamp(0.0, 1.0) * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = cos2 * a2b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rs + rp)
+    };
+
+    Color::new(
+        fresnel(eta.r, k.r),
+        fresnel(eta.g, k.g),
+        fresnel(eta.b, k.b),
+    )
+}
+
+// the facet normal that turns `wo` into `wi` on a dielectric, on the side of `wo`
+fn dielectric_half_vector(wo: Vec3, wi: Vec3, eta: f32) -> Vec3 {
+    let m = if wi.z > 0.0 { wo + wi } else { wo + eta * wi };
+    let m = m.normalize();
+    if m.z < 0.0 {
+        -m
+    } else {
+        m
+    }
+}
+
+// Picks a reflected or refracted direction off a rough dielectric, reflecting with
+// probability F so the Fresnel term cancels out of the weight.
+pub fn sample_dielectric(
+    ggx: &Ggx,
+    wo: Vec3,
+    eta: f32,
+    u: (f32, f32),
+    u_fresnel: f32,
+) -> Option<Vec3> {
+    let m = ggx.sample_visible(wo, u.0, u.1);
+    match refract(wo, m, eta) {
+        Some(wi) if u_fresnel >= fresnel_dielectric(wo.dot(&m), eta) => {
+            if wi.z < 0.0 {
+                Some(wi)
+            } else {
+                None
+            }
+        }
+        _ => {
+            let wi = reflect(wo, m);
+            if wi.z > 0.0 {
+                Some(wi)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+// BSDF times the cosine at `wi` of a rough dielectric (Walter et al. 2007)
+pub fn dielectric_eval(ggx: &Ggx, wo: Vec3, wi: Vec3, eta: f32) -> f32 {
+    if wo.z <= 0.0 || wi.z == 0.0 {
+        return 0.0;
+    }
+
+    let m = dielectric_half_vector(wo, wi, eta);
+    let (cos_o, cos_i) = (wo.dot(&m), wi.dot(&m));
+    let f = fresnel_dielectric(cos_o, eta);
+    let dg = ggx.d(m) * ggx.g(wo, wi);
+
+    if wi.z > 0.0 {
+        f * dg / (4.0 * wo.z)
+    } else {
+        // facets that face away from either direction can not refract
+        if cos_o <= 0.0 || cos_i >= 0.0 {
+            return 0.0;
+        }
+        let denom = cos_o + eta * cos_i;
+        (1.0 - f) * dg * eta * eta * (cos_o * cos_i).abs() / (wo.z * denom * denom)
+    }
+}
+
+// density of `sample_dielectric`
+pub fn dielectric_pdf(ggx: &Ggx, wo: Vec3, wi: Vec3, eta: f32) -> f32 {
+    if wo.z <= 0.0 || wi.z == 0.0 {
+        return 0.0;
+    }
+
+    let m = dielectric_half_vector(wo, wi, eta);
+    let (cos_o, cos_i) = (wo.dot(&m), wi.dot(&m));
+    let f = fresnel_dielectric(cos_o, eta);
+
+    if wi.z > 0.0 {
+        f * ggx.visible_pdf(wo, m) / (4.0 * cos_o)
+    } else {
+        if cos_o <= 0.0 || cos_i >= 0.0 {
+            return 0.0;
+        }
+        let denom = cos_o + eta * cos_i;
+        (1.0 - f) * ggx.visible_pdf(wo, m) * eta * eta * cos_i.abs() / (denom * denom)
+    }
+}
+
+// BSDF times the cosine at `wi` of a GGX mirror, without the Fresnel term
+pub fn reflection_eval(ggx: &Ggx, wo: Vec3, wi: Vec3) -> f32 {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return 0.0;
+    }
+    let m = (wo + wi).normalize();
+    ggx.d(m) * ggx.g(wo, wi) / (4.0 * wo.z)
+}
+
+// density of reflecting `wo` off a visible normal from `Ggx::sample_visible`
+pub fn reflection_pdf(ggx: &Ggx, wo: Vec3, wi: Vec3) -> f32 {
+    if wo.z <= 0.0 || wi.z <= 0.0 {
+        return 0.0;
+    }
+    let m = (wo + wi).normalize();
+    ggx.visible_pdf(wo, m) / (4.0 * wo.dot(&m))
+}
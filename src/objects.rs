@@ -7,23 +7,31 @@ extern crate tobj;
 // use std::default;
 // use std::cell::RefCell;
 // use std::cmp::Ordering;
+use std::cmp::Ordering;
 use std::f32::consts::PI;
 use std::path::Path;
 // use std::sync::Arc;
 
 // use std::ptr::null;
 
-use crate::aabb::Aabb;
+use crate::aabb::{surrounding_box, Aabb};
 use crate::bvh2::BVH;
+use crate::heightfield::Heightfield;
 // use crate::color::BLACK;
-use crate::intersection::Intersection;
+use crate::intersection::{Intersection, Interval};
 // use crate::color::Color;
 // use crate::intersection::Intersection;
 //use crate::color::Color;
-use crate::materials::{Dielectric, Isotropic, Lambertian, Material, Metal, Tracable};
+use crate::materials::{
+    Bumped, Dielectric, Isotropic, Lambertian, Masked, Material, Metal, Principled, Tracable,
+};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::sdf::Sdf;
 
-use crate::texture::SolidColor;
+use crate::texture::{Image, SolidColor, Texture};
+use crate::transform::{interpolate, Keyframe, Transform};
+use image::DynamicImage;
 // use crate::rendering::random_int;
 // use crate::texture::SolidColor;
 use crate::{Color, Vec3};
@@ -32,92 +40,227 @@ use crate::{Color, Vec3};
 #[derive(Clone, Debug)]
 pub enum Object {
     Sphere(Sphere),
-    // MovingSphere(MovingSphere),
+    MovingSphere(MovingSphere),
     Plane(Plane),
     Box(BoxObj),
     ConstantMedium(ConstantMedium),
     Triangle(Triangle),
     BigObject(BigObject),
+    Quad(Quad),
+    InfinitePlane(InfinitePlane),
+    Csg(Csg),
+    Sdf(SdfObject),
+    Heightfield(Heightfield),
+    Animated(Animated),
 }
 pub trait Intersectable {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection>;
-    //fn bounding_box(&self, )
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection>;
 
     fn surface_normal(&self, point: &Vec3, ray: &Ray) -> Vec3;
     fn outward_normal(&self, point: &Vec3, time: f32) -> Vec3;
 
     fn surface_uv(&self, point: &Vec3) -> (f32, f32);
-    fn bounding_box(&self) -> Option<Aabb>;
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb>;
+
+    // solid angle pdf of sampling `direction` from `origin` towards this object
+    fn pdf_value(&self, _origin: &Vec3, _direction: &Vec3) -> f32 {
+        0.0
+    }
+
+    // random direction from `origin` towards this object
+    fn random(&self, _origin: &Vec3, _sampler: &mut dyn Sampler) -> Vec3 {
+        ARBITRARY_NORM
+    }
+
+    // every span of the whole ray line that is inside the object, sorted by distance.
+    // only closed objects have an inside, so open surfaces return nothing
+    fn intervals(&self, _ray: &Ray) -> Vec<Interval<'_>> {
+        Vec::new()
+    }
 }
 
 impl Object {
-    pub fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
         match *self {
-            Object::Sphere(ref obj) => obj.intersects(ray, t_min, t_max),
-            // Object::MovingSphere(ref obj) => obj.intersects(ray, t_min, t_max),
-            Object::Plane(ref obj) => obj.intersects(ray, t_min, t_max),
-            Object::Box(ref obj) => obj.intersects(ray, t_min, t_max),
-            Object::ConstantMedium(ref obj) => obj.intersects(ray, t_min, t_max),
-            Object::Triangle(ref obj) => obj.intersects(ray, t_min, t_max),
-            Object::BigObject(ref obj) => obj.intersects(ray, t_min, t_max),
+            Object::Sphere(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::MovingSphere(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Plane(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Box(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::ConstantMedium(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Triangle(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::BigObject(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Quad(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::InfinitePlane(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Csg(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Sdf(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Heightfield(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+            Object::Animated(ref obj) => obj.intersects(ray, t_min, t_max, sampler),
+        }
+    }
+
+    // closest hit that passes the alpha test of its material, see `Masked`
+    pub fn intersects_opaque(
+        &self,
+        ray: &Ray,
+        mut t_min: f32,
+        t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        loop {
+            let hit = self.intersects(ray, t_min, t_max, sampler)?;
+            if hit.alpha_test(sampler) {
+                return Some(hit);
+            }
+            // step past the cut out hit and look behind it
+            t_min = hit.distance + 1e-4 * hit.distance.max(1.0);
         }
     }
 
     pub fn surface_normal(&self, point: &Vec3, ray: &Ray) -> Vec3 {
         match *self {
             Object::Sphere(ref obj) => obj.surface_normal(point, ray),
-            // Object::MovingSphere(ref obj) => obj.surface_normal(point, ray),
+            Object::MovingSphere(ref obj) => obj.surface_normal(point, ray),
             //    Object::BVHNode(ref obj) => obj.surface_normal(point),
             Object::Plane(ref obj) => obj.surface_normal(point, ray),
             Object::Box(ref _obj) => Vec3::zero(),
             Object::ConstantMedium(ref obj) => obj.surface_normal(point, ray),
             Object::Triangle(ref obj) => obj.surface_normal(point, ray),
             Object::BigObject(ref _obj) => Vec3::zero(),
+            Object::Quad(ref obj) => obj.surface_normal(point, ray),
+            Object::InfinitePlane(ref obj) => obj.surface_normal(point, ray),
+            Object::Csg(ref _obj) => Vec3::zero(),
+            Object::Sdf(ref obj) => obj.surface_normal(point, ray),
+            Object::Heightfield(ref obj) => obj.surface_normal(point, ray),
+            Object::Animated(ref _obj) => Vec3::zero(),
         }
     }
 
     pub fn surface_uv(&self, point: &Vec3) -> (f32, f32) {
         match *self {
             Object::Sphere(ref obj) => obj.surface_uv(point),
-            // Object::MovingSphere(ref obj) => obj.surface_uv(point),
+            Object::MovingSphere(ref obj) => obj.surface_uv(point),
             //    Object::BVHNode(ref obj) => obj.surface_normal(point),
             Object::Plane(ref obj) => obj.surface_uv(point),
             Object::Box(ref _obj) => (0.0, 0.0),
             Object::ConstantMedium(ref obj) => obj.surface_uv(point),
             Object::Triangle(ref obj) => obj.surface_uv(point),
             Object::BigObject(ref _obj) => (0.0, 0.0),
+            Object::Quad(ref obj) => obj.surface_uv(point),
+            Object::InfinitePlane(ref obj) => obj.surface_uv(point),
+            Object::Csg(ref _obj) => (0.0, 0.0),
+            Object::Sdf(ref obj) => obj.surface_uv(point),
+            Object::Heightfield(ref obj) => obj.surface_uv(point),
+            Object::Animated(ref _obj) => (0.0, 0.0),
         }
     }
 
     pub fn outward_normal(&self, point: &Vec3, time: f32) -> Vec3 {
         match *self {
             Object::Sphere(ref obj) => obj.outward_normal(point, time),
-            // Object::MovingSphere(ref obj) => obj.outward_normal(point, time),
+            Object::MovingSphere(ref obj) => obj.outward_normal(point, time),
             //   Object::BVHNode(ref obj) => obj.outward_normal(point, time),
             Object::Plane(ref obj) => obj.outward_normal(point, time),
             Object::Box(ref _obj) => Vec3::zero(),
             Object::ConstantMedium(ref obj) => obj.outward_normal(point, time),
             Object::Triangle(ref obj) => obj.outward_normal(point, time),
             Object::BigObject(ref _obj) => Vec3::zero(),
+            Object::Quad(ref obj) => obj.outward_normal(point, time),
+            Object::InfinitePlane(ref obj) => obj.outward_normal(point, time),
+            Object::Csg(ref _obj) => Vec3::zero(),
+            Object::Sdf(ref obj) => obj.outward_normal(point, time),
+            Object::Heightfield(ref obj) => obj.outward_normal(point, time),
+            Object::Animated(ref _obj) => Vec3::zero(),
         }
     }
 
-    pub fn bounding_box(&self) -> Option<Aabb> {
+    pub fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
         match *self {
-            Object::Sphere(ref obj) => obj.bounding_box(),
-            // Object::MovingSphere(ref obj) => obj.bounding_box(time_0, time_1),
+            Object::Sphere(ref obj) => obj.bounding_box(time0, time1),
+            Object::MovingSphere(ref obj) => obj.bounding_box(time0, time1),
             // Object::BVHNode(ref obj) => obj.bounding_box(time_0, time_1),
-            Object::Plane(ref obj) => obj.bounding_box(),
-            Object::Box(ref obj) => obj.bounding_box(),
-            Object::ConstantMedium(ref obj) => obj.bounding_box(),
-            Object::Triangle(ref obj) => obj.bounding_box(),
-            Object::BigObject(ref obj) => obj.bounding_box(),
+            Object::Plane(ref obj) => obj.bounding_box(time0, time1),
+            Object::Box(ref obj) => obj.bounding_box(time0, time1),
+            Object::ConstantMedium(ref obj) => obj.bounding_box(time0, time1),
+            Object::Triangle(ref obj) => obj.bounding_box(time0, time1),
+            Object::BigObject(ref obj) => obj.bounding_box(time0, time1),
+            Object::Quad(ref obj) => obj.bounding_box(time0, time1),
+            Object::InfinitePlane(ref obj) => obj.bounding_box(time0, time1),
+            Object::Csg(ref obj) => obj.bounding_box(time0, time1),
+            Object::Sdf(ref obj) => obj.bounding_box(time0, time1),
+            Object::Heightfield(ref obj) => obj.bounding_box(time0, time1),
+            Object::Animated(ref obj) => obj.bounding_box(time0, time1),
+        }
+    }
+
+    pub fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        match *self {
+            Object::Sphere(ref obj) => obj.pdf_value(origin, direction),
+            Object::MovingSphere(ref obj) => obj.pdf_value(origin, direction),
+            Object::Plane(ref obj) => obj.pdf_value(origin, direction),
+            Object::Box(ref _obj) => 0.0,
+            Object::ConstantMedium(ref obj) => obj.pdf_value(origin, direction),
+            Object::Triangle(ref obj) => obj.pdf_value(origin, direction),
+            Object::BigObject(ref _obj) => 0.0,
+            Object::Quad(ref obj) => obj.pdf_value(origin, direction),
+            Object::InfinitePlane(ref obj) => obj.pdf_value(origin, direction),
+            Object::Csg(ref _obj) => 0.0,
+            Object::Sdf(ref obj) => obj.pdf_value(origin, direction),
+            Object::Heightfield(ref obj) => obj.pdf_value(origin, direction),
+            Object::Animated(ref _obj) => 0.0,
+        }
+    }
+
+    pub fn random(&self, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        match *self {
+            Object::Sphere(ref obj) => obj.random(origin, sampler),
+            Object::MovingSphere(ref obj) => obj.random(origin, sampler),
+            Object::Plane(ref obj) => obj.random(origin, sampler),
+            Object::Box(ref _obj) => ARBITRARY_NORM,
+            Object::ConstantMedium(ref obj) => obj.random(origin, sampler),
+            Object::Triangle(ref obj) => obj.random(origin, sampler),
+            Object::BigObject(ref _obj) => ARBITRARY_NORM,
+            Object::Quad(ref obj) => obj.random(origin, sampler),
+            Object::InfinitePlane(ref obj) => obj.random(origin, sampler),
+            Object::Csg(ref _obj) => ARBITRARY_NORM,
+            Object::Sdf(ref obj) => obj.random(origin, sampler),
+            Object::Heightfield(ref obj) => obj.random(origin, sampler),
+            Object::Animated(ref _obj) => ARBITRARY_NORM,
+        }
+    }
+
+    pub fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        match *self {
+            Object::Sphere(ref obj) => obj.intervals(ray),
+            Object::MovingSphere(ref obj) => obj.intervals(ray),
+            Object::Plane(ref obj) => obj.intervals(ray),
+            Object::Box(ref obj) => obj.intervals(ray),
+            Object::ConstantMedium(ref obj) => obj.intervals(ray),
+            Object::Triangle(ref obj) => obj.intervals(ray),
+            Object::BigObject(ref _obj) => Vec::new(),
+            Object::Quad(ref obj) => obj.intervals(ray),
+            Object::InfinitePlane(ref obj) => obj.intervals(ray),
+            Object::Csg(ref obj) => obj.intervals(ray),
+            Object::Sdf(ref obj) => obj.intervals(ray),
+            Object::Heightfield(ref obj) => obj.intervals(ray),
+            Object::Animated(ref obj) => obj.intervals(ray),
         }
     }
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Sphere {
     pub center: Vec3,
     pub radius: f32,
@@ -134,8 +277,23 @@ impl Sphere {
     }
 }
 
+// derivatives of a sphere point along the uv of `surface_uv`, `n` is the unit
+// outward normal
+fn sphere_tangents(n: Vec3, radius: f32) -> (Vec3, Vec3) {
+    let sin_theta = (n.x * n.x + n.z * n.z).sqrt().max(1e-6);
+    let dpdu = 2.0 * PI * radius * Vec3::new(n.z, 0.0, -n.x);
+    let dpdv = PI * radius * Vec3::new(-n.x * n.y / sin_theta, sin_theta, -n.y * n.z / sin_theta);
+    (dpdu, dpdv)
+}
+
 impl Intersectable for Sphere {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
         let oc = ray.origin - self.center;
         let a = ray.direction.norm();
         let half_b = oc.dot(&ray.direction);
@@ -158,14 +316,18 @@ impl Intersectable for Sphere {
         let point = ray.at(root);
         let outward_normal = self.outward_normal(&point, ray.time);
 
-        Some(Intersection::new(
-            root,
-            point,
-            self.surface_normal(&point, ray),
-            outward_normal,
-            &self.material,
-            self.surface_uv(&outward_normal),
-        ))
+        let (dpdu, dpdv) = sphere_tangents(outward_normal, self.radius);
+        Some(
+            Intersection::new(
+                root,
+                point,
+                self.surface_normal(&point, ray),
+                outward_normal,
+                &self.material,
+                self.surface_uv(&outward_normal),
+            )
+            .with_tangents(dpdu, dpdv),
+        )
     }
 
     fn surface_normal(&self, point: &Vec3, _ray: &Ray) -> Vec3 {
@@ -183,12 +345,177 @@ impl Intersectable for Sphere {
         (phi / (2.0 * PI), theta / PI)
     }
 
-    fn bounding_box(&self) -> Option<Aabb> {
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
         Some(Aabb {
             min: self.center - Vec3::new(self.radius, self.radius, self.radius),
             max: self.center + Vec3::new(self.radius, self.radius, self.radius),
         })
     }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let oc = ray.origin - self.center;
+        let a = ray.direction.norm();
+        let half_b = oc.dot(&ray.direction);
+        let c = oc.norm() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+
+        if discriminant <= 0.0 {
+            return Vec::new();
+        }
+
+        let sqrtd = discriminant.sqrt();
+        let hit = |root: f32| {
+            let point = ray.at(root);
+            let outward_normal = self.outward_normal(&point, ray.time);
+            let (dpdu, dpdv) = sphere_tangents(outward_normal, self.radius);
+            Intersection::new(
+                root,
+                point,
+                self.surface_normal(&point, ray),
+                outward_normal,
+                &self.material,
+                self.surface_uv(&outward_normal),
+            )
+            .with_tangents(dpdu, dpdv)
+        };
+
+        vec![Interval {
+            enter: hit((-half_b - sqrtd) / a),
+            exit: hit((-half_b + sqrtd) / a),
+        }]
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct MovingSphere {
+    pub center0: Vec3,
+    pub center1: Vec3,
+    pub time0: f32,
+    pub time1: f32,
+    pub radius: f32,
+    pub material: Material,
+}
+
+impl MovingSphere {
+    // moves linearly from `center0` at `time0` to `center1` at `time1`
+    pub fn new(
+        center0: Vec3,
+        center1: Vec3,
+        time0: f32,
+        time1: f32,
+        radius: f32,
+        material: Material,
+    ) -> Object {
+        Object::MovingSphere(MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
+        })
+    }
+
+    pub fn center(&self, time: f32) -> Vec3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        self.center0
+            + ((time - self.time0) / (self.time1 - self.time0)) * (self.center1 - self.center0)
+    }
+
+    fn roots(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let oc = ray.origin - self.center(ray.time);
+        let a = ray.direction.norm();
+        let half_b = oc.dot(&ray.direction);
+        let c = oc.norm() - self.radius * self.radius;
+        let discriminant = half_b * half_b - a * c;
+
+        if discriminant <= 0.0 {
+            return None;
+        }
+
+        let sqrtd = discriminant.sqrt();
+        Some(((-half_b - sqrtd) / a, (-half_b + sqrtd) / a))
+    }
+
+    fn hit(&self, ray: &Ray, root: f32) -> Intersection<'_> {
+        let point = ray.at(root);
+        let outward_normal = self.outward_normal(&point, ray.time);
+
+        let (dpdu, dpdv) = sphere_tangents(outward_normal, self.radius);
+        Intersection::new(
+            root,
+            point,
+            outward_normal,
+            outward_normal,
+            &self.material,
+            self.surface_uv(&outward_normal),
+        )
+        .with_tangents(dpdu, dpdv)
+    }
+}
+
+impl Intersectable for MovingSphere {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        let (near, far) = self.roots(ray)?;
+        if near >= t_min && near <= t_max {
+            Some(self.hit(ray, near))
+        } else if far >= t_min && far <= t_max {
+            Some(self.hit(ray, far))
+        } else {
+            None
+        }
+    }
+
+    fn surface_normal(&self, point: &Vec3, ray: &Ray) -> Vec3 {
+        (*point - self.center(ray.time)).normalize()
+    }
+
+    fn outward_normal(&self, point: &Vec3, time: f32) -> Vec3 {
+        (*point - self.center(time)) / self.radius
+    }
+
+    fn surface_uv(&self, outward_normal: &Vec3) -> (f32, f32) {
+        let theta = (-outward_normal.y).acos();
+        let phi = (-outward_normal.z).atan2(outward_normal.x) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    // covers the sphere at both ends of the shutter interval
+    fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let r = Vec3::from_one(self.radius);
+        let (c0, c1) = (self.center(time0), self.center(time1));
+
+        Some(surrounding_box(
+            &Aabb {
+                min: c0 - r,
+                max: c0 + r,
+            },
+            &Aabb {
+                min: c1 - r,
+                max: c1 + r,
+            },
+        ))
+    }
+
+    fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        match self.roots(ray) {
+            Some((near, far)) => vec![Interval {
+                enter: self.hit(ray, near),
+                exit: self.hit(ray, far),
+            }],
+            None => Vec::new(),
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -200,7 +527,7 @@ pub enum PlaneType {
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Plane {
     plane_type: PlaneType,
     a0: f32,
@@ -242,7 +569,13 @@ impl Plane {
 }
 
 impl Intersectable for Plane {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
         let (k_axis, a_axis, b_axis) = Plane::get_axis(&self.plane_type);
 
         // let ray_origin = ray.origin.to_vec();
@@ -262,14 +595,22 @@ impl Intersectable for Plane {
                 let u = (a - self.a0) / (self.a1 - self.a0);
                 let v = (b - self.b0) / (self.b1 - self.b0);
 
-                Some(Intersection::new(
-                    t,
-                    point,
-                    self.surface_normal(&point, ray),
-                    point,
-                    &self.material,
-                    (u, v),
-                ))
+                let mut dpdu = Vec3::zero();
+                let mut dpdv = Vec3::zero();
+                dpdu[a_axis] = self.a1 - self.a0;
+                dpdv[b_axis] = self.b1 - self.b0;
+
+                Some(
+                    Intersection::new(
+                        t,
+                        point,
+                        self.surface_normal(&point, ray),
+                        self.outward_normal(&point, ray.time),
+                        &self.material,
+                        (u, v),
+                    )
+                    .with_tangents(dpdu, dpdv),
+                )
             }
         }
     }
@@ -290,8 +631,11 @@ impl Intersectable for Plane {
         normal
     }
 
-    fn outward_normal(&self, point: &Vec3, _time: f32) -> Vec3 {
-        *point
+    fn outward_normal(&self, _point: &Vec3, _time: f32) -> Vec3 {
+        let (k_axis, _a_axis, _b_axis) = Plane::get_axis(&self.plane_type);
+        let mut normal = Vec3::zero();
+        normal[k_axis] = 1.0;
+        normal
     }
 
     fn surface_uv(&self, point: &Vec3) -> (f32, f32) {
@@ -307,7 +651,7 @@ impl Intersectable for Plane {
         )
     }
 
-    fn bounding_box(&self) -> Option<Aabb> {
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
         match self.plane_type {
             PlaneType::YZ => Some(Aabb {
                 min: Vec3::new(self.k - 1e-4, self.a0, self.b0),
@@ -325,95 +669,740 @@ impl Intersectable for Plane {
     }
 }
 
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct Quad {
+    pub q: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    normal: Vec3,
+    d: f32,
+    w: Vec3,
+    area: f32,
+    material: Material,
+}
+
+impl Quad {
+    // parallelogram with corner `q` spanned by the edges `u` and `v`
+    pub fn new(q: Vec3, u: Vec3, v: Vec3, material: Material) -> Object {
+        let n = u.cross(&v);
+        let normal = n.normalize();
+
+        Object::Quad(Quad {
+            q,
+            u,
+            v,
+            normal,
+            d: normal.dot(&q),
+            w: n / n.norm(),
+            area: n.length(),
+            material,
+        })
+    }
+
+    fn planar_coordinates(&self, point: &Vec3) -> (f32, f32) {
+        let planar_hit = *point - self.q;
+        (
+            self.w.dot(&planar_hit.cross(&self.v)),
+            self.w.dot(&self.u.cross(&planar_hit)),
+        )
+    }
+
+    // distance along the ray and planar coordinates of a hit, pure geometry so
+    // light sampling can use it without a sampler
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+        let denom = self.normal.dot(&ray.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - self.normal.dot(&ray.origin)) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let (alpha, beta) = self.planar_coordinates(&ray.at(t));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+        Some((t, alpha, beta))
+    }
+}
+
+impl Intersectable for Quad {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        let (t, alpha, beta) = self.hit(ray, t_min, t_max)?;
+        let point = ray.at(t);
+
+        Some(
+            Intersection::new(
+                t,
+                point,
+                self.surface_normal(&point, ray),
+                self.outward_normal(&point, ray.time),
+                &self.material,
+                (alpha, beta),
+            )
+            .with_tangents(self.u, self.v),
+        )
+    }
+
+    fn surface_normal(&self, _point: &Vec3, ray: &Ray) -> Vec3 {
+        if ray.front_face(&self.normal) {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
+    fn outward_normal(&self, _point: &Vec3, _time: f32) -> Vec3 {
+        self.normal
+    }
+
+    fn surface_uv(&self, point: &Vec3) -> (f32, f32) {
+        self.planar_coordinates(point)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        let corners = [
+            self.q,
+            self.q + self.u,
+            self.q + self.v,
+            self.q + self.u + self.v,
+        ];
+
+        let mut min = Vec3::from_one(f32::MAX);
+        let mut max = Vec3::from_one(f32::MIN);
+        for corner in corners.iter() {
+            for a in 0..3 {
+                min[a] = min[a].min(corner[a]);
+                max[a] = max[a].max(corner[a]);
+            }
+        }
+
+        // pad axis aligned quads so the box never has zero thickness
+        for a in 0..3 {
+            if max[a] - min[a] < 1e-4 {
+                min[a] -= 1e-4;
+                max[a] += 1e-4;
+            }
+        }
+
+        Some(Aabb { min, max })
+    }
+
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let ray = Ray::new(*origin, *direction, 0.0);
+        match self.hit(&ray, 0.001, f32::MAX) {
+            Some((t, _, _)) => {
+                let distance_squared = t * t * direction.norm();
+                let cosine = (direction.dot(&self.normal) / direction.length()).abs();
+                distance_squared / (cosine * self.area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn random(&self, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let (a, b) = sampler.next_2d();
+        let p = self.q + a * self.u + b * self.v;
+        p - *origin
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct InfinitePlane {
+    pub point: Vec3,
+    pub normal: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    material: Material,
+}
+
+impl InfinitePlane {
+    // unbounded plane through `point`, it has no bounding box so it can not go in a BVH
+    pub fn new(point: Vec3, normal: Vec3, material: Material) -> Object {
+        let normal = normal.normalize();
+        let helper = if normal.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let tangent = helper.cross(&normal).normalize();
+        let bitangent = normal.cross(&tangent);
+
+        Object::InfinitePlane(InfinitePlane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        })
+    }
+}
+
+impl Intersectable for InfinitePlane {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        let denom = self.normal.dot(&ray.direction);
+        if denom.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.point - ray.origin).dot(&self.normal) / denom;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = ray.at(t);
+
+        Some(
+            Intersection::new(
+                t,
+                point,
+                self.surface_normal(&point, ray),
+                self.outward_normal(&point, ray.time),
+                &self.material,
+                self.surface_uv(&point),
+            )
+            .with_tangents(self.tangent, self.bitangent),
+        )
+    }
+
+    fn surface_normal(&self, _point: &Vec3, ray: &Ray) -> Vec3 {
+        if ray.front_face(&self.normal) {
+            self.normal
+        } else {
+            -self.normal
+        }
+    }
+
+    fn outward_normal(&self, _point: &Vec3, _time: f32) -> Vec3 {
+        self.normal
+    }
+
+    // tiles the unit square once per world unit
+    fn surface_uv(&self, point: &Vec3) -> (f32, f32) {
+        let local = *point - self.point;
+        (
+            local.dot(&self.tangent).rem_euclid(1.0),
+            local.dot(&self.bitangent).rem_euclid(1.0),
+        )
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        None
+    }
+
+    // the plane covers exactly one hemisphere as seen from any point off of it
+    fn pdf_value(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        let t = (self.point - *origin).dot(&self.normal) / self.normal.dot(direction);
+        if t > 0.001 && t.is_finite() {
+            1.0 / (2.0 * PI)
+        } else {
+            0.0
+        }
+    }
+
+    fn random(&self, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let towards = (self.point - *origin).dot(&self.normal).signum() * self.normal;
+        sampler.in_hemisphere(towards).normalize()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BoxObj {
     pub min: Vec3,
     pub max: Vec3,
     pub faces: Vec<Object>,
+    pub material: Material,
 }
 
 impl BoxObj {
-    pub fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
-        ray.trace(&self.faces, t_min, t_max)
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        ray.trace(&self.faces, t_min, t_max, sampler)
     }
 
-    pub fn bounding_box(&self) -> Option<Aabb> {
+    pub fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
         Some(Aabb {
             min: self.min,
             max: self.max,
         })
     }
 
+    pub fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let mut t_enter = f32::MIN;
+        let mut t_exit = f32::MAX;
+        let mut enter_axis = 0;
+        let mut exit_axis = 0;
+
+        for a in 0..3 {
+            let t0 = (self.min[a] - ray.origin[a]) / ray.direction[a];
+            let t1 = (self.max[a] - ray.origin[a]) / ray.direction[a];
+            if t0.min(t1) > t_enter {
+                t_enter = t0.min(t1);
+                enter_axis = a;
+            }
+            if t0.max(t1) < t_exit {
+                t_exit = t0.max(t1);
+                exit_axis = a;
+            }
+        }
+
+        if t_exit <= t_enter {
+            return Vec::new();
+        }
+
+        let hit = |t: f32, axis: usize, entering: bool| {
+            let point = ray.at(t);
+            let mut outward_normal = Vec3::zero();
+            outward_normal[axis] = if entering {
+                -ray.direction[axis].signum()
+            } else {
+                ray.direction[axis].signum()
+            };
+
+            let (a_axis, b_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+            let uv = (
+                (point[a_axis] - self.min[a_axis]) / (self.max[a_axis] - self.min[a_axis]),
+                (point[b_axis] - self.min[b_axis]) / (self.max[b_axis] - self.min[b_axis]),
+            );
+
+            let mut dpdu = Vec3::zero();
+            let mut dpdv = Vec3::zero();
+            dpdu[a_axis] = self.max[a_axis] - self.min[a_axis];
+            dpdv[b_axis] = self.max[b_axis] - self.min[b_axis];
+
+            Intersection::new(
+                t,
+                point,
+                if entering {
+                    outward_normal
+                } else {
+                    -outward_normal
+                },
+                outward_normal,
+                &self.material,
+                uv,
+            )
+            .with_tangents(dpdu, dpdv)
+        };
+
+        vec![Interval {
+            enter: hit(t_enter, enter_axis, true),
+            exit: hit(t_exit, exit_axis, false),
+        }]
+    }
+
     pub fn new(min: Vec3, max: Vec3, material: Material) -> Object {
         Object::Box(BoxObj {
             min,
             max,
-            faces: create_box(min, max, material),
+            faces: create_box(min, max, material.clone()),
+            material,
         })
     }
 }
 
 pub fn create_box(min: Vec3, max: Vec3, material: Material) -> Vec<Object> {
+    let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+    let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+    let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+    // edges are ordered so every face normal points out of the box
     vec![
-        Plane::new(
-            PlaneType::ZX,
-            min.z,
-            max.z,
-            min.x,
-            max.x,
-            min.y,
-            material.clone(),
-        ),
-        Plane::new(
-            PlaneType::XY,
-            min.x,
-            max.x,
-            min.y,
-            max.y,
-            max.z,
-            material.clone(),
-        ),
-        Plane::new(
-            PlaneType::XY,
-            min.x,
-            max.x,
-            min.y,
-            max.y,
-            min.z,
-            material.clone(),
-        ),
-        // bruh
-        Plane::new(
-            PlaneType::ZX,
-            min.z,
-            max.z,
-            min.x,
-            max.x,
-            max.y,
-            material.clone(),
-        ),
-        // bruh
-        Plane::new(
-            PlaneType::YZ,
-            min.y,
-            max.y,
-            min.z,
-            max.z,
-            max.x,
-            material.clone(),
-        ),
-        Plane::new(
-            PlaneType::YZ,
-            min.y,
-            max.y,
-            min.z,
-            max.z,
-            min.x,
-            material.clone(),
-        ),
+        Quad::new(Vec3::new(min.x, min.y, max.z), dx, dy, material.clone()),
+        Quad::new(Vec3::new(max.x, min.y, max.z), -dz, dy, material.clone()),
+        Quad::new(Vec3::new(max.x, min.y, min.z), -dx, dy, material.clone()),
+        Quad::new(Vec3::new(min.x, min.y, min.z), dz, dy, material.clone()),
+        Quad::new(Vec3::new(min.x, max.y, max.z), dx, -dz, material.clone()),
+        Quad::new(Vec3::new(min.x, min.y, min.z), dx, dz, material),
     ]
 }
 
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(&self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_left || in_right,
+            CsgOperation::Intersection => in_left && in_right,
+            CsgOperation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+// boolean combination of two closed objects (Sphere, BoxObj or another Csg)
+#[derive(Clone, Debug)]
+pub struct Csg {
+    pub left: Box<Object>,
+    pub right: Box<Object>,
+    pub operation: CsgOperation,
+}
+
+impl Csg {
+    pub fn new(left: Object, right: Object, operation: CsgOperation) -> Object {
+        Object::Csg(Csg {
+            left: Box::new(left),
+            right: Box::new(right),
+            operation,
+        })
+    }
+
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        self.intervals(ray)
+            .into_iter()
+            .flat_map(|interval| vec![interval.enter, interval.exit])
+            .find(|hit| hit.distance >= t_min && hit.distance <= t_max)
+    }
+
+    pub fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let left = self.left.bounding_box(time0, time1)?;
+        let right = self.right.bounding_box(time0, time1)?;
+
+        match self.operation {
+            CsgOperation::Union => Some(surrounding_box(&left, &right)),
+            CsgOperation::Intersection => Some(Aabb {
+                min: Vec3::new(
+                    left.min.x.max(right.min.x),
+                    left.min.y.max(right.min.y),
+                    left.min.z.max(right.min.z),
+                ),
+                max: Vec3::new(
+                    left.max.x.min(right.max.x),
+                    left.max.y.min(right.max.y),
+                    left.max.z.min(right.max.z),
+                ),
+            }),
+            CsgOperation::Difference => Some(left),
+        }
+    }
+
+    pub fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        // (hit, from the right child, entering that child)
+        let mut events: Vec<(Intersection, bool, bool)> = Vec::new();
+        for interval in self.left.intervals(ray) {
+            events.push((interval.enter, false, true));
+            events.push((interval.exit, false, false));
+        }
+        for interval in self.right.intervals(ray) {
+            events.push((interval.enter, true, true));
+            events.push((interval.exit, true, false));
+        }
+        events.sort_by(|a, b| {
+            a.0.distance
+                .partial_cmp(&b.0.distance)
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let mut in_left = false;
+        let mut in_right = false;
+        let mut enter: Option<Intersection> = None;
+        let mut result = Vec::new();
+
+        for (mut hit, from_right, entering) in events {
+            let was_inside = self.operation.inside(in_left, in_right);
+            if from_right {
+                in_right = entering;
+            } else {
+                in_left = entering;
+            }
+            let is_inside = self.operation.inside(in_left, in_right);
+
+            if was_inside == is_inside {
+                continue;
+            }
+
+            // the surface of a subtracted object faces into the hole it carves
+            if from_right {
+                if let CsgOperation::Difference = self.operation {
+                    hit.outward_normal = -hit.outward_normal;
+                }
+            }
+            // children differ in which way `normal` points, so face it against the ray
+            hit.normal = if ray.front_face(&hit.outward_normal) {
+                hit.outward_normal
+            } else {
+                -hit.outward_normal
+            };
+
+            if is_inside {
+                enter = Some(hit);
+            } else if let Some(enter) = enter.take() {
+                result.push(Interval { enter, exit: hit });
+            }
+        }
+
+        result
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SdfObject {
+    pub sdf: Sdf,
+    pub bbox: Aabb,
+    pub material: Material,
+    pub max_steps: u32,
+    pub epsilon: f32,
+    // fraction of the distance bound taken per step, lower it for fields like twists
+    // that underestimate the true distance poorly
+    pub step_scale: f32,
+}
+
+impl SdfObject {
+    // `bbox` must enclose the visible surface, the field is only marched inside it
+    pub fn new(sdf: Sdf, bbox: Aabb, material: Material) -> Object {
+        Object::Sdf(SdfObject {
+            sdf,
+            bbox,
+            material,
+            max_steps: 256,
+            epsilon: 1e-4,
+            step_scale: 1.0,
+        })
+    }
+
+    // offset for the normal's finite differences, wider than `epsilon` so float
+    // noise in the field does not show up in the normal, and wider still far away
+    fn normal_step(&self, distance: f32) -> f32 {
+        4.0 * self.epsilon + 1e-4 * distance
+    }
+}
+
+impl Intersectable for SdfObject {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        let (t_start, t_end) = self.bbox.hit_range(ray, t_min, t_max)?;
+        let inv_len = ray.direction.length().recip();
+
+        // anything closer is the surface the ray is leaving
+        let t_clear = t_min + self.epsilon * inv_len;
+
+        let mut t = t_start;
+        let mut previous = self.sdf.distance(ray.at(t));
+        for _ in 0..self.max_steps {
+            let point = ray.at(t);
+            let distance = self.sdf.distance(point);
+            // a step that jumped over the surface flips the sign
+            let crossed = distance.signum() != previous.signum();
+
+            if (distance.abs() < self.epsilon || crossed) && t > t_clear {
+                let outward_normal = self.sdf.gradient(point, self.normal_step(t / inv_len));
+
+                return Some(Intersection::new(
+                    t,
+                    point,
+                    if ray.front_face(&outward_normal) {
+                        outward_normal
+                    } else {
+                        -outward_normal
+                    },
+                    outward_normal,
+                    &self.material,
+                    self.surface_uv(&outward_normal),
+                ));
+            }
+
+            previous = distance;
+            // at least epsilon so rays leaving the surface get off of it
+            t += distance.abs().max(self.epsilon) * self.step_scale * inv_len;
+            if t > t_end {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    fn surface_normal(&self, point: &Vec3, ray: &Ray) -> Vec3 {
+        let normal = self.outward_normal(point, ray.time);
+        if ray.front_face(&normal) {
+            normal
+        } else {
+            -normal
+        }
+    }
+
+    fn outward_normal(&self, point: &Vec3, _time: f32) -> Vec3 {
+        self.sdf.gradient(*point, self.normal_step(0.0))
+    }
+
+    fn surface_uv(&self, outward_normal: &Vec3) -> (f32, f32) {
+        let theta = (-outward_normal.y).acos();
+        let phi = (-outward_normal.z).atan2(outward_normal.x) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
+        Some(self.bbox)
+    }
+}
+
+// Wraps an object in a transform keyframed over time, rays are moved into the
+// object's space at their own time so the object blurs across the shutter.
+#[derive(Clone, Debug)]
+pub struct Animated {
+    pub object: Box<Object>,
+    pub keyframes: Vec<Keyframe>,
+}
+
+// shutter samples per keyframe segment when bounding rotating objects, the boxes in
+// between are padded so fewer samples only give looser bounds
+const ANIMATED_BOUND_STEPS: usize = 16;
+
+impl Animated {
+    pub fn new(object: Object, mut keyframes: Vec<Keyframe>) -> Object {
+        assert!(!keyframes.is_empty(), "animated object needs a keyframe");
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+
+        Object::Animated(Animated {
+            object: Box::new(object),
+            keyframes,
+        })
+    }
+
+    pub fn transform_at(&self, time: f32) -> Transform {
+        interpolate(&self.keyframes, time).to_transform()
+    }
+
+    fn local_ray(transform: &Transform, ray: &Ray) -> Ray {
+        Ray::new(
+            transform.inverse_point(&ray.origin),
+            transform.inverse_vector(&ray.direction),
+            ray.time,
+        )
+    }
+
+    fn to_world<'a>(
+        transform: &Transform,
+        ray: &Ray,
+        mut hit: Intersection<'a>,
+    ) -> Intersection<'a> {
+        hit.point = ray.at(hit.distance);
+        hit.normal = transform.normal(&hit.normal);
+        hit.outward_normal = transform.normal(&hit.outward_normal);
+        hit.dpdu = transform.vector(&hit.dpdu);
+        hit.dpdv = transform.vector(&hit.dpdv);
+        hit
+    }
+
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        let transform = self.transform_at(ray.time);
+        let local = Animated::local_ray(&transform, ray);
+
+        self.object
+            .intersects(&local, t_min, t_max, sampler)
+            .map(|hit| Animated::to_world(&transform, ray, hit))
+    }
+
+    pub fn intervals(&self, ray: &Ray) -> Vec<Interval<'_>> {
+        let transform = self.transform_at(ray.time);
+        let local = Animated::local_ray(&transform, ray);
+
+        self.object
+            .intervals(&local)
+            .into_iter()
+            .map(|interval| Interval {
+                enter: Animated::to_world(&transform, ray, interval.enter),
+                exit: Animated::to_world(&transform, ray, interval.exit),
+            })
+            .collect()
+    }
+
+    pub fn bounding_box(&self, time0: f32, time1: f32) -> Option<Aabb> {
+        let local = self.object.bounding_box(time0, time1)?;
+        let largest = |v: Vec3| v.x.abs().max(v.y.abs()).max(v.z.abs());
+        // farthest any point of the object gets from its own origin, before scaling
+        let reach = Vec3::new(
+            local.min.x.abs().max(local.max.x.abs()),
+            local.min.y.abs().max(local.max.y.abs()),
+            local.min.z.abs().max(local.max.z.abs()),
+        )
+        .length();
+
+        let mut times: Vec<f32> = (0..=ANIMATED_BOUND_STEPS)
+            .map(|i| time0 + (time1 - time0) * i as f32 / ANIMATED_BOUND_STEPS as f32)
+            .collect();
+        for pair in self.keyframes.windows(2) {
+            let (start, end) = (pair[0].time.max(time0), pair[1].time.min(time1));
+            if start < end {
+                times.extend(
+                    (0..=ANIMATED_BOUND_STEPS)
+                        .map(|i| start + (end - start) * i as f32 / ANIMATED_BOUND_STEPS as f32),
+                );
+            }
+        }
+        times.sort_by(|a, b| a.total_cmp(b));
+        times.dedup();
+
+        let bound_at = |time: f32| self.transform_at(time).bounding_box(&local);
+        let bbox = times
+            .windows(2)
+            .map(|pair| {
+                let a = interpolate(&self.keyframes, pair[0]);
+                let b = interpolate(&self.keyframes, pair[1]);
+                // A point strays from the line joining where it starts and ends by at most
+                // step^2 / 8 times its acceleration. Angles and scale are linear over the
+                // step, so only turning, and turning while scaling, accelerate it.
+                let d = b.rotation - a.rotation;
+                let turn = (d.x.abs() + d.y.abs() + d.z.abs()).to_radians();
+                let scale = largest(a.scale).max(largest(b.scale));
+                let growth = largest(b.scale - a.scale);
+                let pad = Vec3::from_one(reach * turn * (turn * scale + 2.0 * growth) / 8.0);
+
+                let ends = surrounding_box(&bound_at(pair[0]), &bound_at(pair[1]));
+                Aabb {
+                    min: ends.min - pad,
+                    max: ends.max + pad,
+                }
+            })
+            .fold(bound_at(times[0]), |a, b| surrounding_box(&a, &b));
+        Some(bbox)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Debug)]
 pub struct ConstantMedium {
@@ -439,11 +1428,17 @@ const ARBITRARY_NORM: Vec3 = Vec3 {
 };
 
 impl Intersectable for ConstantMedium {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
-        if let Some(mut hit1) = self.boundary.intersects(&ray, -f32::MAX, f32::MAX) {
-            if let Some(mut hit2) = self
-                .boundary
-                .intersects(&ray, hit1.distance + 0.0001, f32::MAX)
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
+        if let Some(mut hit1) = self.boundary.intersects(&ray, -f32::MAX, f32::MAX, sampler) {
+            if let Some(mut hit2) =
+                self.boundary
+                    .intersects(&ray, hit1.distance + 0.0001, f32::MAX, sampler)
             {
                 if hit1.distance < t_min {
                     hit1.distance = t_min
@@ -454,7 +1449,7 @@ impl Intersectable for ConstantMedium {
                 if hit1.distance < hit2.distance {
                     let distance_inside_boundary =
                         (hit2.distance - hit1.distance) * ray.direction.norm();
-                    let hit_distance = -(1.0 / self.density) * rand::random::<f32>().ln();
+                    let hit_distance = -(1.0 / self.density) * (1.0 - sampler.next_1d()).ln();
                     if hit_distance < distance_inside_boundary {
                         let distance = hit1.distance + hit_distance / ray.direction.norm();
                         let point = ray.at(distance);
@@ -466,6 +1461,9 @@ impl Intersectable for ConstantMedium {
                             outward_normal,
                             mat: &self.phase_function,
                             uv: hit2.uv,
+                            object_id: 0,
+                            dpdu: Vec3::zero(),
+                            dpdv: Vec3::zero(),
                         });
                     }
                 }
@@ -482,7 +1480,7 @@ impl Intersectable for ConstantMedium {
         Vec3::zero()
     }
 
-    fn bounding_box(&self) -> Option<Aabb> {
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
         Some(self.boundary.bbox)
     }
 
@@ -496,6 +1494,8 @@ pub struct Triangle {
     v1: Vec3,
     v2: Vec3,
     normal: Vec3,
+    // texture coordinates of each vertex
+    uvs: [(f32, f32); 3],
     material: Material,
 }
 
@@ -506,6 +1506,7 @@ impl Triangle {
             v1,
             v2,
             normal: (v1 - v0).cross(&(v2 - v0)).normalize(),
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
             material,
         }
     }
@@ -522,13 +1523,44 @@ impl Triangle {
             v1,
             v2,
             normal,
+            uvs: [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
             material,
         }
     }
+
+    pub fn with_uvs(mut self, uvs: [(f32, f32); 3]) -> Triangle {
+        self.uvs = uvs;
+        self
+    }
+
+    // solves for dpdu and dpdv from the uv of the vertices, a degenerate mapping
+    // falls back to the edges
+    fn tangents(&self) -> (Vec3, Vec3) {
+        let [uv0, uv1, uv2] = self.uvs;
+        let (du02, dv02) = (uv0.0 - uv2.0, uv0.1 - uv2.1);
+        let (du12, dv12) = (uv1.0 - uv2.0, uv1.1 - uv2.1);
+        let (dp02, dp12) = (self.v0 - self.v2, self.v1 - self.v2);
+
+        let det = du02 * dv12 - dv02 * du12;
+        if det.abs() < 1e-8 {
+            return (self.v1 - self.v0, self.v2 - self.v0);
+        }
+        let inv_det = 1.0 / det;
+        (
+            (dv12 * dp02 - dv02 * dp12) * inv_det,
+            (du02 * dp12 - du12 * dp02) * inv_det,
+        )
+    }
 }
 
 impl Intersectable for Triangle {
-    fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
+    fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        _sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
         let v0v1 = self.v1 - self.v0;
         let v0v2 = self.v2 - self.v0;
         let pvec = ray.direction.cross(&v0v2);
@@ -558,6 +1590,9 @@ impl Intersectable for Triangle {
         }
 
         let p = ray.at(t);
+        let [uv0, uv1, uv2] = self.uvs;
+        let w = 1.0 - u - v;
+        let (dpdu, dpdv) = self.tangents();
 
         return Some(Intersection {
             distance: t,
@@ -565,7 +1600,13 @@ impl Intersectable for Triangle {
             normal: self.normal,
             outward_normal: self.outward_normal(&p, 0.0),
             mat: &self.material,
-            uv: (u, v),
+            uv: (
+                w * uv0.0 + u * uv1.0 + v * uv2.0,
+                w * uv0.1 + u * uv1.1 + v * uv2.1,
+            ),
+            object_id: 0,
+            dpdu,
+            dpdv,
         });
     }
 
@@ -577,7 +1618,7 @@ impl Intersectable for Triangle {
         (0.0, 0.0)
     }
 
-    fn bounding_box(&self) -> Option<Aabb> {
+    fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
         Some(Aabb {
             min: Vec3::new(
                 self.v0.x.min(self.v1.x.min(self.v2.x)),
@@ -605,10 +1646,15 @@ pub fn load_obj(path: &Path, origin: Vec3, scale: f32, default_mat: Material) ->
     // let default_mat: Material = Lambertian::new(SolidColor::new(Color::new(0.6, 0.6, 0.6)));
 
     let mtls = mtls.unwrap_or_default();
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
 
     let materials: Vec<Material> = mtls
         .iter()
         .map(|m| match m.illumination_model {
+            // materials written with the PBR extension
+            _ if m.unknown_param.contains_key("Pr") || m.unknown_param.contains_key("Pm") => {
+                Principled::from_mtl(m)
+            }
             Some(7) => Dielectric::new(m.optical_density),
             Some(5) => Metal::new(
                 SolidColor::new(Color::new(m.diffuse[0], m.diffuse[1], m.diffuse[2])),
@@ -620,10 +1666,22 @@ pub fn load_obj(path: &Path, origin: Vec3, scale: f32, default_mat: Material) ->
                 m.diffuse[2],
             ))),
         })
+        .zip(mtls.iter())
+        .map(|(mat, m)| mtl_maps(mat, m, dir, scale))
         .collect();
 
     for m in models.iter() {
         let mesh = &m.mesh;
+        let uv_indices = if mesh.texcoord_indices.is_empty() {
+            &mesh.indices
+        } else {
+            &mesh.texcoord_indices
+        };
+        let uv = |f: usize, k: usize| {
+            let i = uv_indices[3 * f + k] as usize;
+            (mesh.texcoords[2 * i], mesh.texcoords[2 * i + 1])
+        };
+
         for f in 0..mesh.indices.len() / 3 {
             let i0 = mesh.indices[3 * f] as usize;
             let i1 = mesh.indices[3 * f + 1] as usize;
@@ -645,8 +1703,8 @@ pub fn load_obj(path: &Path, origin: Vec3, scale: f32, default_mat: Material) ->
             );
 
             let mat: Material = match mesh.material_id {
-                Some(id) => materials[id],
-                None => default_mat,
+                Some(id) => materials[id].clone(),
+                None => default_mat.clone(),
             };
 
             let tri: Triangle;
@@ -661,6 +1719,12 @@ pub fn load_obj(path: &Path, origin: Vec3, scale: f32, default_mat: Material) ->
             tri = Triangle::new(v0, v1, v2, mat);
             // }
 
+            let tri = if mesh.texcoords.is_empty() {
+                tri
+            } else {
+                tri.with_uvs([uv(f, 0), uv(f, 1), uv(f, 2)])
+            };
+
             world.push(Object::Triangle(tri));
         }
     }
@@ -668,17 +1732,77 @@ pub fn load_obj(path: &Path, origin: Vec3, scale: f32, default_mat: Material) ->
     world
 }
 
+// wraps `mat` in the normal, bump and opacity maps of `m`
+fn mtl_maps(mat: Material, m: &tobj::Material, dir: &Path, scale: f32) -> Material {
+    let mat = if let Some((map, _)) = m
+        .unknown_param
+        .get("norm")
+        .and_then(|s| mtl_texture(dir, s))
+    {
+        Bumped::normal_map(mat, map)
+    } else if let Some((height, bm)) = mtl_texture(dir, &m.normal_texture) {
+        // heights are taken as hundredths of a model unit
+        Bumped::bump(mat, height, 0.01 * bm * scale)
+    } else {
+        mat
+    };
+
+    let alpha = match m.dissolve_texture.split_whitespace().last() {
+        Some(file) => load_texture(dir, file, Image::alpha),
+        None => None,
+    };
+    match (alpha, m.illumination_model) {
+        // glass models use `d` for how much light goes through, not for cut outs
+        (_, Some(4 | 6 | 7 | 9)) => mat,
+        (Some(alpha), _) => Masked::new(mat, alpha, m.dissolve),
+        (None, _) if m.dissolve < 1.0 => {
+            Masked::new(mat, SolidColor::new(Color::new(1.0, 1.0, 1.0)), m.dissolve)
+        }
+        _ => mat,
+    }
+}
+
+// Loads the image of an MTL texture statement like `-bm 0.5 bumps.png` as a
+// data texture, along with its `-bm` multiplier. Paths are relative to `dir`.
+fn mtl_texture(dir: &Path, statement: &str) -> Option<(Texture, f32)> {
+    let tokens: Vec<&str> = statement.split_whitespace().collect();
+    let file = tokens.last()?;
+    let bm = tokens
+        .windows(2)
+        .find(|w| w[0] == "-bm")
+        .and_then(|w| w[1].parse().ok())
+        .unwrap_or(1.0);
+
+    Some((load_texture(dir, file, Image::linear)?, bm))
+}
+
+fn load_texture(dir: &Path, file: &str, texture: fn(&DynamicImage) -> Texture) -> Option<Texture> {
+    match image::open(dir.join(file)) {
+        Ok(img) => Some(texture(&img)),
+        Err(err) => {
+            println!("Could not load texture {}: {}", file, err);
+            None
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct BigObject {
     pub objects: BVH,
 }
 
 impl BigObject {
-    pub fn intersects(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Intersection> {
-        self.objects.intersects(ray, t_min, t_max)
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection> {
+        self.objects.intersects(ray, t_min, t_max, sampler)
     }
 
-    pub fn bounding_box(&self) -> Option<Aabb> {
+    pub fn bounding_box(&self, _time0: f32, _time1: f32) -> Option<Aabb> {
         Some(self.objects.bbox)
     }
 
@@ -696,3 +1820,44 @@ pub fn to_bvh(objects: Vec<Object>) -> BVH {
 
     BVH::new(b_objects, 0.0, 1.0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler::IndependentSampler;
+
+    #[test]
+    fn sphere_minus_box_normals_face_the_ray() {
+        let material = Lambertian::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)));
+        let csg = Csg::new(
+            Sphere::new(Vec3::zero(), 1.0, material.clone()),
+            BoxObj::new(
+                Vec3::new(-0.5, -0.5, -0.5),
+                Vec3::new(0.5, 0.5, 0.5),
+                material,
+            ),
+            CsgOperation::Difference,
+        );
+        let ray = Ray::new(Vec3::new(0.1, 0.2, -5.0), Vec3::new(0.0, 0.0, 1.0), 0.0);
+
+        let intervals = match csg {
+            Object::Csg(ref csg) => csg.intervals(&ray),
+            _ => unreachable!(),
+        };
+        assert_eq!(intervals.len(), 2);
+        for interval in intervals.iter() {
+            for hit in [interval.enter, interval.exit].iter() {
+                assert!(hit.normal.dot(&ray.direction) < 0.0);
+            }
+        }
+
+        // coming back out of the hole into what is left of the sphere
+        let reentry = intervals[1].enter;
+        assert!((reentry.distance - 5.5).abs() < 1e-4);
+        assert!(reentry.outward_normal.z < 0.0);
+
+        let mut sampler = IndependentSampler::new(0);
+        let hit = csg.intersects(&ray, 4.5, f32::MAX, &mut sampler).unwrap();
+        assert!(hit.normal.dot(&ray.direction) < 0.0);
+    }
+}
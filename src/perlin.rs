@@ -1,3 +1,120 @@
-//use rand::Rng;
+use crate::rendering::{random_float, random_int};
+use crate::vec3::Vec3;
 
-pub struct Perlin {}
+const POINT_COUNT: usize = 256;
+
+// several KB of tables, share it behind a reference or an `Arc`
+#[derive(Clone, Debug)]
+pub struct Perlin {
+    ranvec: [Vec3; POINT_COUNT],
+    perm_x: [usize; POINT_COUNT],
+    perm_y: [usize; POINT_COUNT],
+    perm_z: [usize; POINT_COUNT],
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Perlin::new()
+    }
+}
+
+impl Perlin {
+    pub fn new() -> Perlin {
+        let mut ranvec = [Vec3::zero(); POINT_COUNT];
+        for v in ranvec.iter_mut() {
+            *v = Vec3::new(
+                random_float(-1.0, 1.0),
+                random_float(-1.0, 1.0),
+                random_float(-1.0, 1.0),
+            )
+            .normalize();
+        }
+
+        Perlin {
+            ranvec,
+            perm_x: Perlin::generate_perm(),
+            perm_y: Perlin::generate_perm(),
+            perm_z: Perlin::generate_perm(),
+        }
+    }
+
+    fn generate_perm() -> [usize; POINT_COUNT] {
+        let mut p = [0; POINT_COUNT];
+        for (i, v) in p.iter_mut().enumerate() {
+            *v = i;
+        }
+        for i in (1..POINT_COUNT).rev() {
+            let target = random_int(0, i as u32 + 1) as usize;
+            p.swap(i, target);
+        }
+        p
+    }
+
+    // smooth gradient noise in roughly [-1, 1]
+    pub fn noise(&self, p: Vec3) -> f32 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i32, fy as i32, fz as i32);
+
+        let uu = u * u * (3.0 - 2.0 * u);
+        let vv = v * v * (3.0 - 2.0 * v);
+        let ww = w * w * (3.0 - 2.0 * w);
+
+        let mut accum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let c = self.ranvec[self.perm_x[((i + di) & 255) as usize]
+                        ^ self.perm_y[((j + dj) & 255) as usize]
+                        ^ self.perm_z[((k + dk) & 255) as usize]];
+
+                    let (fi, fj, fk) = (di as f32, dj as f32, dk as f32);
+                    let weight = Vec3::new(u - fi, v - fj, w - fk);
+                    accum += (fi * uu + (1.0 - fi) * (1.0 - uu))
+                        * (fj * vv + (1.0 - fj) * (1.0 - vv))
+                        * (fk * ww + (1.0 - fk) * (1.0 - ww))
+                        * c.dot(&weight);
+                }
+            }
+        }
+
+        accum
+    }
+
+    pub fn turbulence(&self, p: Vec3, depth: u32) -> f32 {
+        let mut accum = 0.0;
+        let mut temp_p = p;
+        let mut weight = 1.0;
+
+        for _ in 0..depth {
+            accum += weight * self.noise(temp_p);
+            weight *= 0.5;
+            temp_p = temp_p * 2.0;
+        }
+
+        accum.abs()
+    }
+
+    // fractal noise sampled on an nx by nz grid and remapped to [0, 1]
+    pub fn grid(&self, nx: usize, nz: usize, frequency: f32, octaves: u32) -> Vec<f32> {
+        let mut heights = Vec::with_capacity(nx * nz);
+        for j in 0..nz {
+            for i in 0..nx {
+                let mut p = Vec3::new(i as f32 * frequency, 0.0, j as f32 * frequency);
+                let mut weight = 0.5;
+                let mut h = 0.0;
+                for _ in 0..octaves {
+                    h += weight * self.noise(p);
+                    weight *= 0.5;
+                    p = p * 2.0;
+                }
+                heights.push(h);
+            }
+        }
+
+        let min = heights.iter().cloned().fold(f32::MAX, f32::min);
+        let max = heights.iter().cloned().fold(f32::MIN, f32::max);
+        let range = (max - min).max(1e-6);
+        heights.iter().map(|h| (h - min) / range).collect()
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use std::time::{Duration, Instant};
+
+use crate::color::Color;
+use crate::film::Film;
+use crate::rendering::{Camera, RenderSettings};
+use crate::world::World;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// set while a progressive render runs, Ctrl-C quits right away otherwise
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static HANDLER: Once = Once::new();
+
+// ctrlc only allows one handler per process, so it is installed once. The first
+// Ctrl-C during a progressive render raises a flag that every tile checks, a
+// second one or one outside of it quits like Ctrl-C normally does.
+fn watch_interrupt() {
+    HANDLER.call_once(|| {
+        ctrlc::set_handler(|| {
+            if !ACTIVE.load(Ordering::SeqCst) || INTERRUPTED.swap(true, Ordering::SeqCst) {
+                std::process::exit(130);
+            }
+        })
+        .expect("could not set the Ctrl-C handler");
+    });
+    INTERRUPTED.store(false, Ordering::SeqCst);
+    ACTIVE.store(true, Ordering::SeqCst);
+}
+
+fn stop_watching() {
+    ACTIVE.store(false, Ordering::SeqCst);
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+// Any of the stop conditions ends the render, Ctrl-C always does.
+#[derive(Clone, Debug)]
+pub struct ProgressiveSettings {
+    pub target_spp: Option<u32>,
+    pub time_budget: Option<Duration>,
+    // a snapshot is written whenever either of these has passed since the last one
+    pub snapshot_passes: Option<u32>,
+    pub snapshot_interval: Option<Duration>,
+    pub snapshot_path: String,
+}
+
+impl ProgressiveSettings {
+    pub fn new(snapshot_path: &str) -> ProgressiveSettings {
+        ProgressiveSettings {
+            target_spp: None,
+            time_budget: None,
+            snapshot_passes: None,
+            snapshot_interval: None,
+            snapshot_path: snapshot_path.to_string(),
+        }
+    }
+}
+
+impl Camera {
+    // Renders one sample per pixel per pass over the whole image until a stop
+    // condition hits, the final film is also written to the snapshot path.
+    pub fn progressive_render(
+        &self,
+        world: &World,
+        background: &Color,
+        settings: &RenderSettings,
+        progressive: &ProgressiveSettings,
+    ) -> Film {
+        watch_interrupt();
+
+        let mut pass_settings = settings.clone();
+        pass_settings.samples_per_pixel = 1;
+        pass_settings.adaptive = None;
+        // without a target the strata repeat every `samples_per_pixel` passes
+        pass_settings.total_spp =
+            Some(progressive.target_spp.unwrap_or(settings.samples_per_pixel));
+
+        let mut film = Film::with_aovs(settings.width, settings.height(self), &settings.aovs);
+
+        let start = Instant::now();
+        let mut last_snapshot = (0, Instant::now());
+        let mut passes = 0;
+
+        loop {
+            pass_settings.first_sample = settings.first_sample + passes;
+            let pass = self.tile_render(world, background, &pass_settings, &|_, _| {});
+            film.merge(&pass);
+            passes += 1;
+
+            let interrupted = interrupted();
+            let finished = interrupted
+                || progressive.target_spp.is_some_and(|spp| passes >= spp)
+                || progressive
+                    .time_budget
+                    .is_some_and(|budget| start.elapsed() >= budget);
+
+            let snapshot = progressive
+                .snapshot_passes
+                .is_some_and(|n| passes - last_snapshot.0 >= n)
+                || progressive
+                    .snapshot_interval
+                    .is_some_and(|interval| last_snapshot.1.elapsed() >= interval);
+
+            if snapshot || finished {
+                film.to_image().save(&progressive.snapshot_path).unwrap();
+                last_snapshot = (passes, Instant::now());
+                println!("{} passes in {:?}", passes, start.elapsed());
+            }
+
+            if finished {
+                stop_watching();
+                if interrupted {
+                    println!("Interrupted, saved {}", progressive.snapshot_path);
+                }
+                return film;
+            }
+        }
+    }
+}
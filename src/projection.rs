@@ -0,0 +1,209 @@
+use std::f32::consts::PI;
+
+use crate::ray::Ray;
+use crate::rendering::Camera;
+use crate::sampler::Sampler;
+use crate::stereo::Eye;
+use crate::vec3::Vec3;
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum Projection {
+    Perspective(Perspective),
+    Orthographic(Orthographic),
+    Fisheye(Fisheye),
+    Equirectangular(Equirectangular),
+    OmniStereo(OmniStereo),
+}
+
+// maps a point (s, t) in [0, 1] on the image to a ray leaving the camera
+pub trait Projectable {
+    fn get_ray(&self, camera: &Camera, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray;
+}
+
+impl Projectable for Projection {
+    fn get_ray(&self, camera: &Camera, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        match *self {
+            Projection::Perspective(ref p) => p.get_ray(camera, s, t, sampler),
+            Projection::Orthographic(ref p) => p.get_ray(camera, s, t, sampler),
+            Projection::Fisheye(ref p) => p.get_ray(camera, s, t, sampler),
+            Projection::Equirectangular(ref p) => p.get_ray(camera, s, t, sampler),
+            Projection::OmniStereo(ref p) => p.get_ray(camera, s, t, sampler),
+        }
+    }
+}
+
+fn shutter_time(camera: &Camera, sampler: &mut dyn Sampler) -> f32 {
+    if camera.time_1 > camera.time_0 {
+        sampler.range(camera.time_0, camera.time_1)
+    } else {
+        camera.time_0
+    }
+}
+
+// thin lens pinhole projection set up by `Camera::new`
+#[derive(Copy, Clone, Debug)]
+pub struct Perspective {}
+
+impl Perspective {
+    pub fn new() -> Projection {
+        Projection::Perspective(Perspective {})
+    }
+}
+
+impl Projectable for Perspective {
+    fn get_ray(&self, camera: &Camera, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        let rd = camera.lens_sample(s, t, sampler);
+        let offset = camera.u * rd.x + camera.v * rd.y;
+
+        Ray::new(
+            camera.origin + offset,
+            camera.llc + s * camera.horizontal + t * camera.vertical - camera.origin - offset,
+            shutter_time(camera, sampler),
+        )
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Orthographic {
+    // height of the view in world units, the width follows the aspect ratio
+    pub height: f32,
+}
+
+impl Orthographic {
+    pub fn new(height: f32) -> Projection {
+        Projection::Orthographic(Orthographic { height })
+    }
+}
+
+impl Projectable for Orthographic {
+    fn get_ray(&self, camera: &Camera, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        let width = self.height * camera.aspect_ratio;
+        let origin =
+            camera.origin + (s - 0.5) * width * camera.u + (t - 0.5) * self.height * camera.v;
+
+        Ray::new(origin, -camera.w, shutter_time(camera, sampler))
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum FisheyeMapping {
+    // image radius grows linearly with the angle off axis
+    Equidistant,
+    // equal solid angles cover equal image areas
+    Equisolid,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Fisheye {
+    // field of view across the circle inscribed in the image, up to 360 degrees.
+    // pixels outside of the circle keep following the mapping
+    pub fov: f32,
+    pub mapping: FisheyeMapping,
+}
+
+impl Fisheye {
+    pub fn new(fov: f32, mapping: FisheyeMapping) -> Projection {
+        Projection::Fisheye(Fisheye { fov, mapping })
+    }
+}
+
+impl Projectable for Fisheye {
+    fn get_ray(&self, camera: &Camera, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        let (x, y) = if camera.aspect_ratio >= 1.0 {
+            ((2.0 * s - 1.0) * camera.aspect_ratio, 2.0 * t - 1.0)
+        } else {
+            (2.0 * s - 1.0, (2.0 * t - 1.0) / camera.aspect_ratio)
+        };
+        let r = (x * x + y * y).sqrt();
+        let phi = y.atan2(x);
+
+        let theta_max = (self.fov / 2.0).to_radians();
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * theta_max,
+            FisheyeMapping::Equisolid => {
+                2.0 * (r * (theta_max / 2.0).sin()).clamp(-1.0, 1.0).asin()
+            }
+        }
+        .min(PI);
+
+        let direction =
+            theta.sin() * (phi.cos() * camera.u + phi.sin() * camera.v) - theta.cos() * camera.w;
+
+        Ray::new(camera.origin, direction, shutter_time(camera, sampler))
+    }
+}
+
+// full 360 by 180 degree latitude/longitude panorama, meant for a 2:1 image
+#[derive(Copy, Clone, Debug)]
+pub struct Equirectangular {}
+
+impl Equirectangular {
+    pub fn new() -> Projection {
+        Projection::Equirectangular(Equirectangular {})
+    }
+
+    pub fn direction(camera: &Camera, s: f32, t: f32) -> Vec3 {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+
+        latitude.cos() * (longitude.sin() * camera.u - longitude.cos() * camera.w)
+            + latitude.sin() * camera.v
+    }
+}
+
+impl Projectable for Equirectangular {
+    fn get_ray(&self, camera: &Camera, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        Ray::new(
+            camera.origin,
+            Equirectangular::direction(camera, s, t),
+            shutter_time(camera, sampler),
+        )
+    }
+}
+
+// Omni-directional stereo: an equirectangular panorama where every column is seen
+// from a point on a circle of diameter `interocular`, so each eye keeps its
+// parallax in every direction.
+#[derive(Copy, Clone, Debug)]
+pub struct OmniStereo {
+    pub interocular: f32,
+    // distance where the two eyes' rays cross, infinite for parallel rays
+    pub convergence: f32,
+    pub eye: Eye,
+}
+
+impl OmniStereo {
+    pub fn new(interocular: f32, convergence: f32, eye: Eye) -> Projection {
+        Projection::OmniStereo(OmniStereo {
+            interocular,
+            convergence,
+            eye,
+        })
+    }
+}
+
+impl Projectable for OmniStereo {
+    fn get_ray(&self, camera: &Camera, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+        let direction = Equirectangular::direction(camera, s, t);
+
+        // fade the eye separation out towards the poles where it can not be kept
+        let right = longitude.cos() * camera.u + longitude.sin() * camera.w;
+        let offset = self.eye.sign() * self.interocular / 2.0 * latitude.cos() * right;
+
+        let direction = if self.convergence.is_finite() {
+            direction * self.convergence - offset
+        } else {
+            direction
+        };
+
+        Ray::new(
+            camera.origin + offset,
+            direction,
+            shutter_time(camera, sampler),
+        )
+    }
+}
@@ -1,21 +1,34 @@
 use crate::bvh::BvhTree;
-use crate::bvh2::BVH;
 use crate::color::BLACK;
 // use crate::intersection;
 use crate::intersection::Intersection;
 use crate::materials::Tracable;
 // use crate::objects::Intersectable;
 use crate::objects::Object;
+use crate::sampler::Sampler;
+use crate::world::World;
 use crate::Color;
 use crate::Vec3;
 use std::cmp::Ordering;
 
 const TEMP_UV: (f32, f32) = (0.0, 0.0);
+
+// weight of a sample picked with density `a` when density `b` could have picked it too
+fn power_heuristic(a: f32, b: f32) -> f32 {
+    let (a2, b2) = (a * a, b * b);
+    if a2 + b2 > 0.0 {
+        a2 / (a2 + b2)
+    } else {
+        0.0
+    }
+}
 //use crate::rendering::random_hemisphere_distribution;
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
     pub time: f32,
+    // nanometers once a dispersive surface has split the path, zero for RGB paths
+    pub wavelength: f32,
 }
 
 impl Ray {
@@ -24,6 +37,21 @@ impl Ray {
             origin,
             direction,
             time,
+            wavelength: 0.0,
+        }
+    }
+
+    pub fn with_wavelength(mut self, wavelength: f32) -> Ray {
+        self.wavelength = wavelength;
+        self
+    }
+
+    // scattered rays keep the wavelength of the path unless the material picked one
+    pub fn inherit_wavelength(self, incoming: &Ray) -> Ray {
+        if self.wavelength == 0.0 {
+            self.with_wavelength(incoming.wavelength)
+        } else {
+            self
         }
     }
 
@@ -36,10 +64,11 @@ impl Ray {
         objects: &'traced Vec<Object>,
         t_min: f32,
         t_max: f32,
+        sampler: &mut dyn Sampler,
     ) -> Option<Intersection<'traced>> {
         objects
             .into_iter()
-            .filter_map(|obj| obj.intersects(self, t_min, t_max))
+            .filter_map(|obj| obj.intersects(self, t_min, t_max, sampler))
             .min_by(|inter1, inter2| {
                 inter1
                     .distance
@@ -86,18 +115,26 @@ impl Ray {
     // }
 
     //    pub fn color(&self, objects: &Vec<Object>, depth: u32) -> Color {
-    pub fn color(&self, world: &Vec<Object>, background: &Color, depth: u32) -> Color {
+    pub fn color(
+        &self,
+        world: &Vec<Object>,
+        background: &Color,
+        depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
         if depth <= 0 {
             return BLACK;
         }
 
-        match self.trace(world, 0.001, ::std::f32::MAX) {
+        match self.trace(world, 0.001, ::std::f32::MAX, sampler) {
             Some(i) => {
                 let mat = &i.mat;
                 let emitted = mat.emitted(TEMP_UV, &i);
-                return match mat.scatter(self, &i) {
+                return match mat.scatter(self, &i, sampler) {
                     Some((attenuation, scattered)) => {
-                        emitted + attenuation * scattered.color(world, background, depth - 1)
+                        let scattered = scattered.inherit_wavelength(self);
+                        emitted
+                            + attenuation * scattered.color(world, background, depth - 1, sampler)
                     }
 
                     None => emitted,
@@ -116,18 +153,27 @@ impl Ray {
         }
     }
 
-    pub fn bvh_color(&self, world: &BvhTree, background: &Color, depth: u32) -> Color {
+    pub fn bvh_color(
+        &self,
+        world: &BvhTree,
+        background: &Color,
+        depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
         if depth <= 0 {
             return BLACK;
         }
 
-        match world.hit(self, 0.001, ::std::f32::MAX) {
+        match world.hit(self, 0.001, ::std::f32::MAX, sampler) {
             Some(i) => {
                 let mat = &i.mat;
                 let emitted = mat.emitted(TEMP_UV, &i);
-                return match mat.scatter(self, &i) {
+                return match mat.scatter(self, &i, sampler) {
                     Some((attenuation, scattered)) => {
-                        emitted + attenuation * scattered.bvh_color(world, background, depth - 1)
+                        let scattered = scattered.inherit_wavelength(self);
+                        emitted
+                            + attenuation
+                                * scattered.bvh_color(world, background, depth - 1, sampler)
                     }
 
                     None => emitted,
@@ -139,7 +185,7 @@ impl Ray {
 
                 // return match mat.scatter(self, point, i.object) {
                 //     Some((attenuation, scattered)) => {
-                //         emitted + attenuation * scattered.bvh_color(world, background, depth - 1)
+                //         emitted + attenuation * scattered.bvh_color(world, background, depth - 1, sampler)
                 //     }
 
                 //     None => emitted,
@@ -158,27 +204,110 @@ impl Ray {
         }
     }
 
-    pub fn bvh2_color(&self, world: &BVH, background: &Color, depth: u32) -> Color {
+    pub fn bvh2_color(
+        &self,
+        world: &World,
+        background: &Color,
+        depth: u32,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        self.bvh2_radiance(world, background, depth, sampler, None)
+    }
+
+    // `bsdf_pdf` is the density the last bounce picked this ray with, when that
+    // bounce also sampled the lights and light found here has to be shared
+    fn bvh2_radiance(
+        &self,
+        world: &World,
+        background: &Color,
+        depth: u32,
+        sampler: &mut dyn Sampler,
+        bsdf_pdf: Option<f32>,
+    ) -> Color {
         if depth <= 0 {
             return BLACK;
         }
 
-        match world.intersects(self, 0.001, ::std::f32::MAX) {
+        match world.intersects(self, 0.001, ::std::f32::MAX, sampler) {
             Some(i) => {
                 let mat = &i.mat;
-                let emitted = mat.emitted(TEMP_UV, &i);
-                return match mat.scatter(self, &i) {
+                let emitted = self.emission_weight(world, bsdf_pdf) * mat.emitted(TEMP_UV, &i);
+                let direct = self.direct_light(world, &i, sampler);
+                return match mat.scatter(self, &i, sampler) {
                     Some((attenuation, scattered)) => {
-                        emitted + attenuation * scattered.bvh2_color(world, background, depth - 1)
+                        let scattered = scattered.inherit_wavelength(self);
+                        let bsdf_pdf = self.bsdf_pdf(world, &i, &scattered);
+                        emitted
+                            + direct
+                            + attenuation
+                                * scattered.bvh2_radiance(
+                                    world,
+                                    background,
+                                    depth - 1,
+                                    sampler,
+                                    bsdf_pdf,
+                                )
                     }
 
-                    None => emitted,
+                    None => emitted + direct,
                 };
             }
             None => *background,
         }
     }
 
+    // share of the emission this ray finds that it keeps, the rest was picked up
+    // by sampling the lights at the bounce it came from
+    pub fn emission_weight(&self, world: &World, bsdf_pdf: Option<f32>) -> f32 {
+        match bsdf_pdf {
+            Some(pdf) => power_heuristic(pdf, world.light_pdf(&self.origin, &self.direction)),
+            None => 1.0,
+        }
+    }
+
+    // density `scattered` was picked with at `i`, none when the lights were not
+    // sampled there, specular bounces have no density and leave light to `scattered`
+    pub fn bsdf_pdf(&self, world: &World, i: &Intersection, scattered: &Ray) -> Option<f32> {
+        let pdf = i.mat.pdf(
+            -self.direction.normalize(),
+            scattered.direction.normalize(),
+            i,
+        );
+        if pdf > 0.0 && !world.lights.is_empty() {
+            Some(pdf)
+        } else {
+            None
+        }
+    }
+
+    // light reaching `i` from a point picked on one of the world's lights
+    pub fn direct_light(
+        &self,
+        world: &World,
+        i: &Intersection,
+        sampler: &mut dyn Sampler,
+    ) -> Color {
+        if world.lights.is_empty() {
+            return BLACK;
+        }
+
+        let direction = world.sample_light(&i.point, sampler);
+        let light_pdf = world.light_pdf(&i.point, &direction);
+        let wo = -self.direction.normalize();
+        let wi = direction.normalize();
+        let bsdf_pdf = i.mat.pdf(wo, wi, i);
+        if light_pdf <= 0.0 || bsdf_pdf <= 0.0 {
+            return BLACK;
+        }
+
+        let shadow = Ray::new(i.point, direction, self.time).inherit_wavelength(self);
+        let light = match world.intersects(&shadow, 0.001, f32::MAX, sampler) {
+            Some(hit) => hit.mat.emitted(TEMP_UV, &hit),
+            None => return BLACK,
+        };
+        i.mat.eval(wo, wi, i) * light * (power_heuristic(light_pdf, bsdf_pdf) / light_pdf)
+    }
+
     // pub fn bvh_both(
     //     &self,
     //     world: &BvhTree,
@@ -193,7 +322,7 @@ impl Ray {
     //             let emitted = mat.emitted(TEMP_UV, &i);
     //             return match mat.scatter(self, &i) {
     //                 Some((attenuation, scattered)) => (
-    //                     emitted + attenuation * scattered.bvh_color(world, background, depth - 1),
+    //                     emitted + attenuation * scattered.bvh_color(world, background, depth - 1, sampler),
     //                     mat.albedo(i.uv, i.outward_normal).to_vec_f32(),
     //                     i.normal.to_vec_f32(),
     //                 ),
@@ -209,8 +338,13 @@ impl Ray {
     //     }
     // }
 
-    pub fn bvh_buffer(&self, world: &BvhTree, background: &Color) -> (Vec<f32>, Vec<f32>) {
-        match world.hit(self, 0.001, ::std::f32::MAX) {
+    pub fn bvh_buffer(
+        &self,
+        world: &BvhTree,
+        background: &Color,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec<f32>, Vec<f32>) {
+        match world.hit(self, 0.001, ::std::f32::MAX, sampler) {
             Some(i) => {
                 // let point = self.at(i.distance);
                 let mat = i.mat;
@@ -227,8 +361,13 @@ impl Ray {
         }
     }
 
-    pub fn bvh2_buffer(&self, world: &BVH, background: &Color) -> (Vec<f32>, Vec<f32>) {
-        match world.intersects(self, 0.001, ::std::f32::MAX) {
+    pub fn bvh2_buffer(
+        &self,
+        world: &World,
+        background: &Color,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec<f32>, Vec<f32>) {
+        match world.intersects(self, 0.001, ::std::f32::MAX, sampler) {
             Some(i) => {
                 // let point = self.at(i.distance);
                 let mat = i.mat;
@@ -245,8 +384,13 @@ impl Ray {
         }
     }
 
-    pub fn buffer(&self, world: &Vec<Object>, background: &Color) -> (Vec<f32>, Vec<f32>) {
-        match self.trace(world, 0.001, ::std::f32::INFINITY) {
+    pub fn buffer(
+        &self,
+        world: &Vec<Object>,
+        background: &Color,
+        sampler: &mut dyn Sampler,
+    ) -> (Vec<f32>, Vec<f32>) {
+        match self.trace(world, 0.001, ::std::f32::INFINITY, sampler) {
             Some(i) => {
                 // let point = self.at(i.distance);
                 let mat = i.mat;
@@ -1,35 +1,51 @@
+use crate::adaptive::AdaptiveSettings;
+use crate::aov::Aov;
 use crate::bvh::BvhTree;
-use crate::bvh2::BVH;
 use crate::color::*;
+use crate::filter::{BoxFilter, Filter, Filterable};
+use crate::lens::{Aperture, PhysicalLens};
 //use crate::intersection::Intersection;
 //use crate::objects::Intersectable;
+use crate::projection::{Perspective, Projectable, Projection};
 use crate::ray::Ray;
+use crate::sampler::{Pcg32, PixelSampler, Sampler, SamplerType};
+use crate::tiles::{tiles, TileOrder};
+use crate::world::World;
 use crate::Object;
 use crate::Vec3;
 use image::imageops::flip_vertical;
 use image::Rgb;
-use image::{ImageBuffer, RgbImage};
+use image::{ImageBuffer, ImageResult, RgbImage};
 use indicatif::{ProgressBar, ProgressStyle};
 // use rand::distributions::{Distribution, Uniform};
-use rand::Rng;
 use rayon::prelude::*;
 // /use std::cmp::Ordering;
+use std::cell::RefCell;
 use std::time::Instant;
 
 // const BETWEEN: Uniform<f32> = Uniform::from(0.0_f32..1.0_f32);
 
+// Used while building scenes. Every thread starts from the same seed so a scene
+// built on one thread comes out the same on every run, rendering goes through a
+// `Sampler` instead.
+thread_local! {
+    static RNG: RefCell<Pcg32> = RefCell::new(Pcg32::new(0, 0));
+}
+
+pub fn seed_random(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = Pcg32::new(seed, 0));
+}
+
 pub fn random_int(i: u32, j: u32) -> u32 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(i..j)
+    i + RNG.with(|rng| rng.borrow_mut().next_u32()) % (j - i)
 }
 
 pub fn random_float(i: f32, j: f32) -> f32 {
-    let mut rng = rand::thread_rng();
-    rng.gen_range(i..j)
+    i + (j - i) * random_distribution()
 }
 
 pub fn random_distribution() -> f32 {
-    rand::random()
+    RNG.with(|rng| rng.borrow_mut().next_f32())
     // let mut rng = rand::thread_rng();
     // BETWEEN.sample(&mut rng)
 }
@@ -90,6 +106,7 @@ pub fn random_in_unit_disk() -> Vec3 {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct Camera {
     pub origin: Vec3,
     pub llc: Vec3,
@@ -102,6 +119,11 @@ pub struct Camera {
     pub aspect_ratio: f32,
     pub time_0: f32,
     pub time_1: f32,
+    pub projection: Projection,
+    pub aperture: Aperture,
+    // optical vignetting, the lens opening gets clipped into a cat's eye towards
+    // the edges of the image. 0 turns it off
+    pub cat_eye: f32,
 }
 
 impl Camera {
@@ -145,30 +167,127 @@ impl Camera {
             aspect_ratio,
             time_0,
             time_1,
+            projection: Perspective::new(),
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
         }
     }
 
-    pub fn get_ray(&self, s: f32, t: f32) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk();
-        let offset = self.u * rd.x + self.v * rd.y;
+    pub fn physical(
+        lookfrom: Vec3,
+        lookat: Vec3,
+        vup: Vec3,
+        lens: &PhysicalLens,
+        focus_dist: f32,
+        time_0: f32,
+        time_1: f32,
+    ) -> Camera {
+        Camera::new(
+            lookfrom,
+            lookat,
+            vup,
+            lens.vfov(),
+            lens.aspect_ratio(),
+            lens.aperture(),
+            focus_dist,
+            time_0,
+            time_1,
+        )
+    }
+
+    pub fn get_ray(&self, s: f32, t: f32, sampler: &mut dyn Sampler) -> Ray {
+        self.projection.get_ray(self, s, t, sampler)
+    }
+
+    // point on the lens for a ray through (s, t), scaled by the lens radius
+    pub fn lens_sample(&self, s: f32, t: f32, sampler: &mut dyn Sampler) -> Vec3 {
+        if self.cat_eye <= 0.0 {
+            return self.lens_radius * self.aperture.sample(sampler);
+        }
 
-        // TODO
-        Ray {
-            origin: self.origin + offset,
-            direction: self.llc + s * self.horizontal + t * self.vertical - self.origin - offset,
-            time: random_float(self.time_0, self.time_1),
+        // The exit pupil seen from off axis clips the opening with a unit circle
+        // pushed towards the edge. The aperture sample is squeezed into the lens
+        // shaped overlap of the two, so clipping takes no extra dimensions.
+        let shift = self.cat_eye * Vec3::new(2.0 * s - 1.0, 2.0 * t - 1.0, 0.0);
+        let p = self.aperture.sample(sampler);
+        let d = shift.length();
+        if d == 0.0 {
+            return self.lens_radius * p;
         }
+        if d >= 2.0 {
+            return Vec3::zero();
+        }
+
+        // along the direction of the clipping circle's center and across it
+        let along = -shift / d;
+        let across = Vec3::new(-along.y, along.x, 0.0);
+        let (a, b) = (p.dot(&along), p.dot(&across).clamp(-1.0, 1.0));
+
+        // half chord of the unit circle at b, and of the overlap at the squeezed b
+        let chord = (1.0 - b * b).sqrt();
+        let b = b * (1.0 - d * d / 4.0).sqrt();
+        let half = (1.0 - b * b).sqrt() - d / 2.0;
+        let u = if chord > 0.0 {
+            (a / chord).clamp(-1.0, 1.0)
+        } else {
+            0.0
+        };
+
+        self.lens_radius * ((d / 2.0 + u * half) * along + b * across)
+    }
+
+    pub fn focus_dist(&self) -> f32 {
+        (self.origin - (self.llc + self.horizontal / 2.0 + self.vertical / 2.0)).dot(&self.w)
+    }
+
+    pub fn set_focus_dist(&mut self, focus_dist: f32) {
+        let old = self.focus_dist();
+        let scale = focus_dist / old;
+        // keep any off-axis shift of the viewport, scaled along with it
+        let offset =
+            self.llc + self.horizontal / 2.0 + self.vertical / 2.0 - (self.origin - old * self.w);
+        let center = self.origin - focus_dist * self.w;
+
+        self.horizontal = self.horizontal * scale;
+        self.vertical = self.vertical * scale;
+        self.llc = center + offset * scale - self.horizontal / 2.0 - self.vertical / 2.0;
+    }
+
+    // Focus on whatever is seen through pixel (x, y) of a `width` wide image.
+    // Returns the new focus distance, or None if the ray escapes the scene.
+    pub fn autofocus(
+        &mut self,
+        world: &World,
+        x: u32,
+        y: u32,
+        width: u32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<f32> {
+        let height = (width as f32 / self.aspect_ratio) as u32;
+        let s = (x as f32 + 0.5) / width as f32;
+        let t = (height as f32 - y as f32 - 0.5) / height as f32;
+
+        let target = self.llc + s * self.horizontal + t * self.vertical;
+        let ray = Ray::new(self.origin, target - self.origin, self.time_0);
+        let inter = world.intersects(&ray, 0.001, f32::INFINITY, sampler)?;
+
+        let focus_dist = (inter.point - self.origin).dot(&-self.w);
+        self.set_focus_dist(focus_dist);
+        Some(focus_dist)
     }
 
     pub fn render(
         &self,
         world: &Vec<Object>,
         background: &Color,
-        width: u32,
-        samples_per_pixel: u32,
-        max_depth: u32,
+        settings: &RenderSettings,
     ) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let (width, samples_per_pixel, max_depth) = (
+            settings.width,
+            settings.samples_per_pixel,
+            settings.max_depth,
+        );
+        let height = settings.height(self);
 
         let mut img = RgbImage::new(width, height);
 
@@ -185,17 +304,20 @@ impl Camera {
         );
         let t1 = Instant::now();
 
+        let mut sampler = settings.create_sampler();
         for y in 0..height {
             for x in 0..width {
                 let mut final_color = BLACK;
 
-                for _ in 0..samples_per_pixel {
-                    let u = (random_distribution() + x as f32) / (width - 1) as f32;
-                    let v = (random_distribution() + y as f32) / (height - 1) as f32;
+                for s in 0..samples_per_pixel {
+                    sampler.start_pixel_sample(x, y, settings.first_sample + s);
+                    let (du, dv) = sampler.next_2d();
+                    let u = (du + x as f32) / (width - 1) as f32;
+                    let v = (dv + y as f32) / (height - 1) as f32;
 
-                    let r = self.get_ray(u, v);
+                    let r = self.get_ray(u, v, &mut sampler);
 
-                    final_color = final_color + r.color(world, background, max_depth);
+                    final_color = final_color + r.color(world, background, max_depth, &mut sampler);
                 }
                 img.put_pixel(
                     x,
@@ -215,12 +337,14 @@ impl Camera {
         &self,
         world: &BvhTree,
         background: &Color,
-        width: u32,
-        samples_per_pixel: u32,
-        max_depth: u32,
-        denoise_settings: Option<DenoiseSettings>,
+        settings: &RenderSettings,
     ) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let (width, samples_per_pixel, max_depth) = (
+            settings.width,
+            settings.samples_per_pixel,
+            settings.max_depth,
+        );
+        let height = settings.height(self);
 
         let mut img = RgbImage::new(width, height);
 
@@ -238,13 +362,16 @@ impl Camera {
             .flat_map(|j| {
                 (0..width).into_par_iter().flat_map(move |i| {
                     let mut col = BLACK;
+                    let mut sampler = settings.create_sampler();
 
-                    for _s in 0..samples_per_pixel {
-                        let u = ((i as f32) + rand::random::<f32>()) / (width as f32);
-                        let v = ((j as f32) + rand::random::<f32>()) / (height as f32);
+                    for s in 0..samples_per_pixel {
+                        sampler.start_pixel_sample(i, j, settings.first_sample + s);
+                        let (du, dv) = sampler.next_2d();
+                        let u = ((i as f32) + du) / (width as f32);
+                        let v = ((j as f32) + dv) / (height as f32);
 
-                        let r = self.get_ray(u, v);
-                        col = col + r.bvh_color(world, background, max_depth);
+                        let r = self.get_ray(u, v, &mut sampler);
+                        col = col + r.bvh_color(world, background, max_depth, &mut sampler);
                     }
 
                     if i % 64 == 0 {
@@ -266,11 +393,11 @@ impl Camera {
         img.save("regular.png").unwrap();
         println!("Finished in {:?}", start.elapsed());
 
-        match denoise_settings {
+        match settings.denoise_settings {
             Some(dns) => {
                 println!("Starting Denoising");
                 let (albedo_buffer, normal_buffer) =
-                    self.bvh_calculate_buffers(world, background, width);
+                    self.bvh_calculate_buffers(world, background, settings);
                 dns.denoise(img, albedo_buffer, normal_buffer)
             }
             None => img,
@@ -279,18 +406,35 @@ impl Camera {
 
     pub fn bvh2_render(
         &self,
-        world: &BVH,
+        world: &World,
         background: &Color,
         width: u32,
         samples_per_pixel: u32,
         max_depth: u32,
         denoise_settings: Option<DenoiseSettings>,
-    ) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let height = (width as f32 / self.aspect_ratio) as u32;
-
-        let mut img = RgbImage::new(width, height);
+    ) -> ImageResult<ImageBuffer<image::Rgb<u8>, Vec<u8>>> {
+        let mut settings = RenderSettings::new(width, samples_per_pixel, max_depth);
+        settings.denoise_settings = denoise_settings;
+        settings.output = Some("regular".to_string());
+        self.settings_render(world, background, &settings)
+    }
 
-        let bar = &Box::new(ProgressBar::new((width * height / 64) as u64));
+    pub fn settings_render(
+        &self,
+        world: &World,
+        background: &Color,
+        settings: &RenderSettings,
+    ) -> ImageResult<ImageBuffer<image::Rgb<u8>, Vec<u8>>> {
+        let height = settings.height(self);
+        let tile_count = tiles(
+            settings.width,
+            height,
+            settings.tile_size,
+            settings.tile_order,
+        )
+        .len();
+
+        let bar = ProgressBar::new(tile_count as u64);
         bar.set_prefix("Rendering");
         bar.set_style(
             ProgressStyle::default_bar()
@@ -298,61 +442,51 @@ impl Camera {
         );
         let start = Instant::now();
 
-        let pixels: Vec<u8> = (0..height)
-            .into_par_iter()
-            .rev()
-            .flat_map(|j| {
-                (0..width).into_par_iter().flat_map(move |i| {
-                    let mut col = BLACK;
-
-                    for _s in 0..samples_per_pixel {
-                        let u = ((i as f32) + rand::random::<f32>()) / (width as f32);
-                        let v = ((j as f32) + rand::random::<f32>()) / (height as f32);
-
-                        let r = self.get_ray(u, v);
-                        col = col + r.bvh2_color(world, background, max_depth);
-                    }
-
-                    if i % 64 == 0 {
-                        bar.inc(1);
-                    }
-
-                    col = (col / samples_per_pixel as f32).sqrt();
-                    let v_col = col.to_vec_f32();
-                    (0..3)
-                        .into_par_iter()
-                        .map(move |k| (255.99 * v_col[k as usize]).min(255.0) as u8)
-                })
-            })
-            .collect();
+        let film = self.tile_render(world, background, settings, &|_, _| bar.inc(1));
 
         bar.finish();
 
-        img.copy_from_slice(&pixels);
-        img.save("regular.png").unwrap();
+        let img = film.to_image();
+        if let Some(output) = &settings.output {
+            img.save(format!("{}.png", output))?;
+            if !settings.aovs.is_empty() {
+                film.save_aovs(&format!("{}_", output))?;
+                let mut exr = film.to_exr();
+                film.add_cryptomattes(&mut exr, world);
+                exr.write(&format!("{}.exr", output))?;
+            }
+            if settings.adaptive.is_some_and(|adaptive| adaptive.heatmap) {
+                film.heatmap().save(format!("{}_heatmap.png", output))?;
+            }
+        }
         println!("Finished in {:?}", start.elapsed());
 
-        match denoise_settings {
+        Ok(match settings.denoise_settings {
             Some(dns) => {
                 println!("Starting Denoising");
-                let (albedo_buffer, normal_buffer) =
-                    self.bvh2_calculate_buffers(world, background, width);
+                // the AOVs already hold the buffers when they were rendered
+                let (albedo_buffer, normal_buffer) = match film.denoise_buffers() {
+                    Some(buffers) => buffers,
+                    None => self.bvh2_calculate_buffers(world, background, settings),
+                };
                 dns.denoise(img, albedo_buffer, normal_buffer)
             }
             None => img,
-        }
+        })
     }
 
     pub fn pog_render(
         &self,
         world: &Vec<Object>,
         background: &Color,
-        width: u32,
-        samples_per_pixel: u32,
-        max_depth: u32,
-        denoise_settings: Option<DenoiseSettings>,
+        settings: &RenderSettings,
     ) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let (width, samples_per_pixel, max_depth) = (
+            settings.width,
+            settings.samples_per_pixel,
+            settings.max_depth,
+        );
+        let height = settings.height(self);
 
         let mut img = RgbImage::new(width, height);
 
@@ -370,12 +504,15 @@ impl Camera {
             .flat_map(|j| {
                 (0..width).into_par_iter().flat_map(move |i| {
                     let mut col = BLACK;
-                    for _s in 0..samples_per_pixel {
-                        let u = ((i as f32) + rand::random::<f32>()) / (width as f32);
-                        let v = ((j as f32) + rand::random::<f32>()) / (height as f32);
-
-                        let r = self.get_ray(u, v);
-                        col = col + r.color(world, background, max_depth);
+                    let mut sampler = settings.create_sampler();
+                    for s in 0..samples_per_pixel {
+                        sampler.start_pixel_sample(i, j, settings.first_sample + s);
+                        let (du, dv) = sampler.next_2d();
+                        let u = ((i as f32) + du) / (width as f32);
+                        let v = ((j as f32) + dv) / (height as f32);
+
+                        let r = self.get_ray(u, v, &mut sampler);
+                        col = col + r.color(world, background, max_depth, &mut sampler);
                     }
 
                     if i % 64 == 0 {
@@ -397,11 +534,11 @@ impl Camera {
 
         println!("Finished in {:?}", start.elapsed());
 
-        match denoise_settings {
+        match settings.denoise_settings {
             Some(dns) => {
                 println!("Starting Denoising");
                 let (albedo_buffer, normal_buffer) =
-                    self.calculate_buffers(world, background, width);
+                    self.calculate_buffers(world, background, settings);
                 dns.denoise(img, albedo_buffer, normal_buffer)
             }
             None => img,
@@ -412,9 +549,10 @@ impl Camera {
         &self,
         world: &BvhTree,
         background: &Color,
-        width: u32,
+        settings: &RenderSettings,
     ) -> (Vec<f32>, Vec<f32>) {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let width = settings.width;
+        let height = settings.height(self);
 
         let bar = ProgressBar::new((width * height) as u64);
 
@@ -430,13 +568,15 @@ impl Camera {
         let mut normal_buffer: Vec<f32> = Vec::new();
         let mut albedo_buffer: Vec<f32> = Vec::new();
 
+        let mut sampler = settings.create_sampler();
         for y in 0..height {
             for x in 0..width {
                 let u = x as f32 / (width - 1) as f32;
                 let v = (height - 1 - y) as f32 / (height - 1) as f32;
-                let r = self.get_ray(u, v);
+                sampler.start_pixel_sample(x, y, settings.first_sample);
+                let r = self.get_ray(u, v, &mut sampler);
 
-                let (albedo, normal) = r.bvh_buffer(world, background);
+                let (albedo, normal) = r.bvh_buffer(world, background, &mut sampler);
                 (albedo).into_iter().for_each(|a| albedo_buffer.push(a));
                 (normal).into_iter().for_each(|n| normal_buffer.push(n));
                 //  albedo_buffer.copy_from_slice(&albedo);
@@ -454,11 +594,12 @@ impl Camera {
 
     pub fn bvh2_calculate_buffers(
         &self,
-        world: &BVH,
+        world: &World,
         background: &Color,
-        width: u32,
+        settings: &RenderSettings,
     ) -> (Vec<f32>, Vec<f32>) {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let width = settings.width;
+        let height = settings.height(self);
 
         let bar = ProgressBar::new((width * height) as u64);
 
@@ -474,13 +615,15 @@ impl Camera {
         let mut normal_buffer: Vec<f32> = Vec::new();
         let mut albedo_buffer: Vec<f32> = Vec::new();
 
+        let mut sampler = settings.create_sampler();
         for y in 0..height {
             for x in 0..width {
                 let u = x as f32 / (width - 1) as f32;
                 let v = (height - 1 - y) as f32 / (height - 1) as f32;
-                let r = self.get_ray(u, v);
+                sampler.start_pixel_sample(x, y, settings.first_sample);
+                let r = self.get_ray(u, v, &mut sampler);
 
-                let (albedo, normal) = r.bvh2_buffer(world, background);
+                let (albedo, normal) = r.bvh2_buffer(world, background, &mut sampler);
                 (albedo).into_iter().for_each(|a| albedo_buffer.push(a));
                 (normal).into_iter().for_each(|n| normal_buffer.push(n));
                 //  albedo_buffer.copy_from_slice(&albedo);
@@ -500,9 +643,10 @@ impl Camera {
         &self,
         world: &Vec<Object>,
         background: &Color,
-        width: u32,
+        settings: &RenderSettings,
     ) -> (Vec<f32>, Vec<f32>) {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let width = settings.width;
+        let height = settings.height(self);
 
         let bar = ProgressBar::new((width * height) as u64);
 
@@ -518,13 +662,15 @@ impl Camera {
         let mut normal_buffer: Vec<f32> = Vec::new();
         let mut albedo_buffer: Vec<f32> = Vec::new();
 
+        let mut sampler = settings.create_sampler();
         for y in 0..height {
             for x in 0..width {
                 let u = x as f32 / (width - 1) as f32;
                 let v = (height - 1 - y) as f32 / (height - 1) as f32;
-                let r = self.get_ray(u, v);
+                sampler.start_pixel_sample(x, y, settings.first_sample);
+                let r = self.get_ray(u, v, &mut sampler);
 
-                let (albedo, normal) = r.buffer(world, background);
+                let (albedo, normal) = r.buffer(world, background, &mut sampler);
                 (albedo).into_iter().for_each(|a| albedo_buffer.push(a));
                 (normal).into_iter().for_each(|n| normal_buffer.push(n));
                 //  albedo_buffer.copy_from_slice(&albedo);
@@ -544,12 +690,13 @@ impl Camera {
         &self,
         world: &Vec<Object>,
         background: &Color,
-        width: u32,
+        settings: &RenderSettings,
     ) -> (
         ImageBuffer<image::Rgb<u8>, Vec<u8>>,
         ImageBuffer<image::Rgb<u8>, Vec<u8>>,
     ) {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let width = settings.width;
+        let height = settings.height(self);
 
         let mut normals = RgbImage::new(width, height);
         let mut albedos = RgbImage::new(width, height);
@@ -568,13 +715,15 @@ impl Camera {
         // let mut normal_buffer: Vec<f32> = Vec::new();
         // let mut albedo_buffer: Vec<f32> = Vec::new();
 
+        let mut sampler = settings.create_sampler();
         for y in 0..height {
             for x in 0..width {
                 let u = x as f32 / (width - 1) as f32;
                 let v = y as f32 / (height - 1) as f32;
-                let r = self.get_ray(u, v);
+                sampler.start_pixel_sample(x, y, settings.first_sample);
+                let r = self.get_ray(u, v, &mut sampler);
 
-                let (albedo, normal) = r.buffer(world, background);
+                let (albedo, normal) = r.buffer(world, background, &mut sampler);
 
                 albedos.put_pixel(
                     x,
@@ -615,12 +764,13 @@ impl Camera {
         &self,
         world: &BvhTree,
         background: &Color,
-        width: u32,
+        settings: &RenderSettings,
     ) -> (
         ImageBuffer<image::Rgb<u8>, Vec<u8>>,
         ImageBuffer<image::Rgb<u8>, Vec<u8>>,
     ) {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let width = settings.width;
+        let height = settings.height(self);
 
         let mut normals = RgbImage::new(width, height);
         let mut albedos = RgbImage::new(width, height);
@@ -639,13 +789,15 @@ impl Camera {
         // let mut normal_buffer: Vec<f32> = Vec::new();
         // let mut albedo_buffer: Vec<f32> = Vec::new();
 
+        let mut sampler = settings.create_sampler();
         for y in 0..height {
             for x in 0..width {
                 let u = x as f32 / (width - 1) as f32;
                 let v = y as f32 / (height - 1) as f32;
-                let r = self.get_ray(u, v);
+                sampler.start_pixel_sample(x, y, settings.first_sample);
+                let r = self.get_ray(u, v, &mut sampler);
 
-                let (albedo, normal) = r.bvh_buffer(world, background);
+                let (albedo, normal) = r.bvh_buffer(world, background, &mut sampler);
 
                 albedos.put_pixel(
                     x,
@@ -686,11 +838,14 @@ impl Camera {
         &self,
         objects: &Vec<Object>,
         background: &Color,
-        width: u32,
-        samples_per_pixel: u32,
-        max_depth: u32,
+        settings: &RenderSettings,
     ) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let (width, samples_per_pixel, max_depth) = (
+            settings.width,
+            settings.samples_per_pixel,
+            settings.max_depth,
+        );
+        let height = settings.height(self);
         let mut img = RgbImage::new(width, height);
 
         let bar = ProgressBar::new((height * width) as u64 + 1);
@@ -707,14 +862,18 @@ impl Camera {
 
         img.par_chunks_mut(3).enumerate().for_each(|(i, slab)| {
             let mut final_color = BLACK;
+            let mut sampler = settings.create_sampler();
+            let (x, y) = (i as u32 % width, i as u32 / width);
 
-            (0..samples_per_pixel).for_each(|_| {
-                let u = (random_distribution() + (i as u32 % width) as f32) / (width - 1) as f32;
-                let v = (random_distribution() + (i as u32 / width) as f32) / (height - 1) as f32;
+            (0..samples_per_pixel).for_each(|s| {
+                sampler.start_pixel_sample(x, y, settings.first_sample + s);
+                let (du, dv) = sampler.next_2d();
+                let u = (du + x as f32) / (width - 1) as f32;
+                let v = (dv + y as f32) / (height - 1) as f32;
 
-                let r = self.get_ray(u, v);
+                let r = self.get_ray(u, v, &mut sampler);
 
-                final_color = final_color + r.color(objects, background, max_depth);
+                final_color = final_color + r.color(objects, background, max_depth, &mut sampler);
             });
             slab.copy_from_slice(&(final_color / samples_per_pixel as f32).sqrt().to_slice());
 
@@ -733,12 +892,14 @@ impl Camera {
         row_h: u32,
         objects: &Vec<Object>,
         background: &Color,
-        width: u32,
-        samples_per_pixel: u32,
-        max_depth: u32,
-        denoise_settings: Option<DenoiseSettings>,
+        settings: &RenderSettings,
     ) -> ImageBuffer<image::Rgb<u8>, Vec<u8>> {
-        let height = (width as f32 / self.aspect_ratio) as u32;
+        let (width, samples_per_pixel, max_depth) = (
+            settings.width,
+            settings.samples_per_pixel,
+            settings.max_depth,
+        );
+        let height = settings.height(self);
         let chunk_size = width * 3 * row_h;
 
         let mut img = RgbImage::new(width, height);
@@ -765,10 +926,7 @@ impl Camera {
                     background,
                     row_h * i as u32,
                     row_h,
-                    width,
-                    height,
-                    samples_per_pixel,
-                    max_depth,
+                    settings,
                 ));
 
                 bar.inc(1);
@@ -779,11 +937,11 @@ impl Camera {
         bar.finish();
         println!("Took {:?}", t1.elapsed());
 
-        match denoise_settings {
+        match settings.denoise_settings {
             Some(dns) => {
                 println!("Starting Denoising");
                 let (albedo_buffer, normal_buffer) =
-                    self.calculate_buffers(objects, background, width);
+                    self.calculate_buffers(objects, background, settings);
                 dns.denoise(img, albedo_buffer, normal_buffer)
             }
             None => img,
@@ -796,23 +954,30 @@ impl Camera {
         background: &Color,
         j: u32,
         h: u32,
-        width: u32,
-        height: u32,
-        samples_per_pixel: u32,
-        max_depth: u32,
+        settings: &RenderSettings,
     ) -> Vec<u8> {
+        let (width, samples_per_pixel, max_depth) = (
+            settings.width,
+            settings.samples_per_pixel,
+            settings.max_depth,
+        );
+        let height = settings.height(self);
         let mut pixels: Vec<u8> = Vec::new();
+        let mut sampler = settings.create_sampler();
         for y in j..(j + h) {
             for x in 0..width {
                 let mut final_color = BLACK;
 
-                (0..samples_per_pixel).for_each(|_| {
-                    let u = (random_distribution() + x as f32) / (width - 1) as f32;
-                    let v = (random_distribution() + y as f32) / (height - 1) as f32;
+                (0..samples_per_pixel).for_each(|s| {
+                    sampler.start_pixel_sample(x, y, settings.first_sample + s);
+                    let (du, dv) = sampler.next_2d();
+                    let u = (du + x as f32) / (width - 1) as f32;
+                    let v = (dv + y as f32) / (height - 1) as f32;
 
-                    let r = self.get_ray(u, v);
+                    let r = self.get_ray(u, v, &mut sampler);
 
-                    final_color = final_color + r.color(objects, background, max_depth);
+                    final_color =
+                        final_color + r.color(objects, background, max_depth, &mut sampler);
                 });
 
                 pixels
@@ -823,6 +988,73 @@ impl Camera {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct RenderSettings {
+    pub width: u32,
+    pub samples_per_pixel: u32,
+    pub max_depth: u32,
+    // the same seed and settings always give the same image
+    pub seed: u64,
+    // index of the first sample taken per pixel, so separate passes do not repeat samples
+    pub first_sample: u32,
+    // samples per pixel of the whole render when it is split into passes, so the
+    // sampler spreads its strata over all of them
+    pub total_spp: Option<u32>,
+    pub sampler: SamplerType,
+    pub filter: Filter,
+    pub tile_size: u32,
+    pub tile_order: TileOrder,
+    // replaces the fixed `samples_per_pixel` when set
+    pub adaptive: Option<AdaptiveSettings>,
+    // extra passes filled in during the render, see `Film::to_exr`
+    pub aovs: Vec<Aov>,
+    pub denoise_settings: Option<DenoiseSettings>,
+    // the image is written to `{output}.png`, AOVs to `{output}_{aov}.png` and
+    // `{output}.exr`, the adaptive heatmap to `{output}_heatmap.png`. Nothing is
+    // written when unset
+    pub output: Option<String>,
+}
+
+impl RenderSettings {
+    pub fn new(width: u32, samples_per_pixel: u32, max_depth: u32) -> RenderSettings {
+        RenderSettings {
+            width,
+            samples_per_pixel,
+            max_depth,
+            seed: 0,
+            first_sample: 0,
+            total_spp: None,
+            sampler: SamplerType::Independent,
+            filter: BoxFilter::new(0.5),
+            tile_size: 32,
+            tile_order: TileOrder::Spiral,
+            adaptive: None,
+            aovs: Vec::new(),
+            denoise_settings: None,
+            output: None,
+        }
+    }
+
+    pub fn height(&self, camera: &Camera) -> u32 {
+        (self.width as f32 / camera.aspect_ratio) as u32
+    }
+
+    // pixels a filter reaches outside of the pixel a sample was taken in
+    pub fn filter_margin(&self) -> u32 {
+        (self.filter.radius() - 0.5).ceil().max(0.0) as u32
+    }
+
+    pub fn create_sampler(&self) -> PixelSampler {
+        let samples_per_pixel = match (self.total_spp, self.adaptive) {
+            (Some(total), _) => total,
+            (None, Some(adaptive)) => adaptive.max_spp,
+            (None, None) => self.samples_per_pixel,
+        };
+        self.sampler.create(self.seed, samples_per_pixel)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
 pub struct DenoiseSettings {
     pub srgb: bool,
     pub hdr: bool,
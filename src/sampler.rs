@@ -0,0 +1,429 @@
+use std::f32::consts::PI;
+
+use crate::vec3::Vec3;
+
+// Source of the random numbers for one camera sample. Renderers restart it for every
+// pixel sample, so a sample's numbers only depend on the seed and where it is taken,
+// never on which thread takes it.
+pub trait Sampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32);
+
+    // uniform in [0, 1)
+    fn next_1d(&mut self) -> f32;
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+
+    fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_1d()
+    }
+
+    // The shapes below map a fixed number of dimensions instead of rejecting, so
+    // every sample uses the same dimensions of the sequence.
+
+    fn in_unit_disk(&mut self) -> Vec3 {
+        let (u1, u2) = self.next_2d();
+        let r = u1.sqrt();
+        let theta = 2.0 * PI * u2;
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.0)
+    }
+
+    fn unit_vector(&mut self) -> Vec3 {
+        let (u1, u2) = self.next_2d();
+        let z = 1.0 - 2.0 * u1;
+        let r = (1.0 - z * z).max(0.0).sqrt();
+        let phi = 2.0 * PI * u2;
+        Vec3::new(r * phi.cos(), r * phi.sin(), z)
+    }
+
+    // cosine weighted around +z
+    fn cosine_hemisphere(&mut self) -> Vec3 {
+        let d = self.in_unit_disk();
+        Vec3::new(d.x, d.y, (1.0 - d.x * d.x - d.y * d.y).max(0.0).sqrt())
+    }
+
+    fn in_unit_sphere(&mut self) -> Vec3 {
+        self.unit_vector() * self.next_1d().cbrt()
+    }
+
+    fn in_hemisphere(&mut self, normal: Vec3) -> Vec3 {
+        let v = self.in_unit_sphere();
+        if v.dot(&normal) > 0.0 {
+            v
+        } else {
+            -v
+        }
+    }
+}
+
+// splitmix64 finalizer
+pub fn mix_bits(mut v: u64) -> u64 {
+    v ^= v >> 31;
+    v = v.wrapping_mul(0x7fb5_d329_728e_a185);
+    v ^= v >> 27;
+    v = v.wrapping_mul(0x81da_def4_bc2d_d44d);
+    v ^= v >> 33;
+    v
+}
+
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |h, v| {
+        mix_bits(h ^ v.wrapping_add(h << 6))
+    })
+}
+
+// PCG32 (O'Neill), small and fast with independent streams
+#[derive(Copy, Clone, Debug)]
+pub struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+impl Pcg32 {
+    pub fn new(seed: u64, stream: u64) -> Pcg32 {
+        let mut rng = Pcg32 {
+            state: 0,
+            inc: (stream << 1) | 1,
+        };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(self.inc);
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        xorshifted.rotate_right((old >> 59) as u32)
+    }
+
+    pub fn next_f32(&mut self) -> f32 {
+        // 24 bits so the result stays below 1.0
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+// plain pseudo random numbers, one stream per pixel sample
+#[derive(Copy, Clone, Debug)]
+pub struct IndependentSampler {
+    pub seed: u64,
+    rng: Pcg32,
+}
+
+impl IndependentSampler {
+    pub fn new(seed: u64) -> IndependentSampler {
+        IndependentSampler {
+            seed,
+            rng: Pcg32::new(seed, 0),
+        }
+    }
+}
+
+impl Sampler for IndependentSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.rng = Pcg32::new(hash(&[self.seed, x as u64, y as u64]), index as u64);
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+}
+
+fn to_unit(bits: u32) -> f32 {
+    (bits >> 8) as f32 / (1 << 24) as f32
+}
+
+// Kensler's hashed permutation: element i of a random permutation of 0..l picked by p
+fn permutation_element(mut i: u32, l: u32, p: u32) -> u32 {
+    let mut w = l - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+// Jittered samples: each dimension is split into one stratum per sample (a grid of
+// them for 2d), and every pixel visits the strata in its own shuffled order.
+#[derive(Copy, Clone, Debug)]
+pub struct StratifiedSampler {
+    pub seed: u64,
+    pub samples_per_pixel: u32,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: u32) -> StratifiedSampler {
+        StratifiedSampler {
+            seed,
+            samples_per_pixel: samples_per_pixel.max(1),
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn next_hash(&mut self) -> u64 {
+        self.dimension += 1;
+        hash(&[self.pixel, self.dimension])
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let h = self.next_hash();
+        let n = self.samples_per_pixel;
+        let stratum = permutation_element(self.index % n, n, h as u32);
+        let jitter = Pcg32::new(h, self.index as u64).next_f32();
+        ((stratum as f32 + jitter) / n as f32).min(1.0 - f32::EPSILON)
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let h = self.next_hash();
+        let side = (self.samples_per_pixel as f32).sqrt().ceil() as u32;
+        let n = side * side;
+        let stratum = permutation_element(self.index % n, n, h as u32);
+        let mut rng = Pcg32::new(h, self.index as u64);
+        (
+            (((stratum % side) as f32 + rng.next_f32()) / side as f32).min(1.0 - f32::EPSILON),
+            (((stratum / side) as f32 + rng.next_f32()) / side as f32).min(1.0 - f32::EPSILON),
+        )
+    }
+}
+
+const PRIMES: [u32; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+fn radical_inverse(base: u32, mut index: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+    let (mut inv, mut result) = (inv_base, 0.0);
+    while index > 0 {
+        result += (index % base) as f64 * inv;
+        index /= base;
+        inv *= inv_base;
+    }
+    result as f32
+}
+
+// Halton sequence with a prime base per dimension, shifted by a random offset per
+// pixel and dimension (Cranley-Patterson rotation) so pixels do not repeat each other.
+// Past the last prime it falls back to independent numbers.
+#[derive(Copy, Clone, Debug)]
+pub struct HaltonSampler {
+    pub seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let h = hash(&[self.pixel, dimension as u64]);
+
+        if dimension >= PRIMES.len() {
+            return Pcg32::new(h, self.index as u64).next_f32();
+        }
+
+        let v = radical_inverse(PRIMES[dimension], self.index) + to_unit(h as u32);
+        (v - v.floor()).min(1.0 - f32::EPSILON)
+    }
+}
+
+// Laine-Karras style hash, an Owen scramble when applied to bit reversed values
+fn laine_karras_permutation(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    laine_karras_permutation(x.reverse_bits(), seed).reverse_bits()
+}
+
+// first two Sobol dimensions, the second is generated by the Pascal matrix
+fn sobol(index: u32, dimension: u32) -> u32 {
+    if dimension == 0 {
+        return index.reverse_bits();
+    }
+
+    let (mut v, mut x) = (1u32 << 31, 0);
+    for bit in 0..32 {
+        if index & (1 << bit) != 0 {
+            x ^= v;
+        }
+        v ^= v >> 1;
+    }
+    x
+}
+
+// Owen scrambled Sobol points (Burley 2020). Every pair of dimensions is a 2d Sobol
+// pattern with its own scramble, and the sample index is shuffled per pair so the
+// pairs stay uncorrelated with each other.
+#[derive(Copy, Clone, Debug)]
+pub struct SobolSampler {
+    pub seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: u64,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler {
+            seed,
+            pixel: 0,
+            index: 0,
+            dimension: 0,
+        }
+    }
+
+    fn shuffled_index(&self) -> u32 {
+        nested_uniform_scramble(self.index, hash(&[self.pixel, self.dimension]) as u32)
+    }
+
+    fn scrambled(&self, index: u32, dimension: u32) -> f32 {
+        let seed = hash(&[self.pixel, self.dimension, dimension as u64 + 1]) as u32;
+        to_unit(nested_uniform_scramble(sobol(index, dimension), seed))
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        self.pixel = hash(&[self.seed, x as u64, y as u64]);
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let v = self.scrambled(self.shuffled_index(), 0);
+        self.dimension += 1;
+        v
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let index = self.shuffled_index();
+        let v = (self.scrambled(index, 0), self.scrambled(index, 1));
+        self.dimension += 1;
+        v
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum SamplerType {
+    Independent,
+    Stratified,
+    Halton,
+    Sobol,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum PixelSampler {
+    Independent(IndependentSampler),
+    Stratified(StratifiedSampler),
+    Halton(HaltonSampler),
+    Sobol(SobolSampler),
+}
+
+impl SamplerType {
+    // stratification needs to know how many samples each pixel will take
+    pub fn create(&self, seed: u64, samples_per_pixel: u32) -> PixelSampler {
+        match self {
+            SamplerType::Independent => PixelSampler::Independent(IndependentSampler::new(seed)),
+            SamplerType::Stratified => {
+                PixelSampler::Stratified(StratifiedSampler::new(seed, samples_per_pixel))
+            }
+            SamplerType::Halton => PixelSampler::Halton(HaltonSampler::new(seed)),
+            SamplerType::Sobol => PixelSampler::Sobol(SobolSampler::new(seed)),
+        }
+    }
+}
+
+impl Sampler for PixelSampler {
+    fn start_pixel_sample(&mut self, x: u32, y: u32, index: u32) {
+        match self {
+            PixelSampler::Independent(s) => s.start_pixel_sample(x, y, index),
+            PixelSampler::Stratified(s) => s.start_pixel_sample(x, y, index),
+            PixelSampler::Halton(s) => s.start_pixel_sample(x, y, index),
+            PixelSampler::Sobol(s) => s.start_pixel_sample(x, y, index),
+        }
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        match self {
+            PixelSampler::Independent(s) => s.next_1d(),
+            PixelSampler::Stratified(s) => s.next_1d(),
+            PixelSampler::Halton(s) => s.next_1d(),
+            PixelSampler::Sobol(s) => s.next_1d(),
+        }
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        match self {
+            PixelSampler::Independent(s) => s.next_2d(),
+            PixelSampler::Stratified(s) => s.next_2d(),
+            PixelSampler::Halton(s) => s.next_2d(),
+            PixelSampler::Sobol(s) => s.next_2d(),
+        }
+    }
+}
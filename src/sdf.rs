@@ -0,0 +1,150 @@
+use crate::vec3::Vec3;
+
+// Small signed distance expression tree, every primitive is centered on the origin
+// and moved around with the transform nodes.
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub enum Sdf {
+    Sphere { radius: f32 },
+    Box { half_extents: Vec3 },
+    // lies in the xz plane around the y axis
+    Torus { major: f32, minor: f32 },
+    Translate { sdf: Box<Sdf>, offset: Vec3 },
+    Scale { sdf: Box<Sdf>, factor: f32 },
+    Round { sdf: Box<Sdf>, radius: f32 },
+    Union(Box<Sdf>, Box<Sdf>),
+    Intersection(Box<Sdf>, Box<Sdf>),
+    Difference(Box<Sdf>, Box<Sdf>),
+    SmoothUnion { a: Box<Sdf>, b: Box<Sdf>, k: f32 },
+    // rotates the xz plane by `rate` radians per unit of y
+    Twist { sdf: Box<Sdf>, rate: f32 },
+    // infinite repetition, axes with a period of 0 are not repeated
+    Repeat { sdf: Box<Sdf>, period: Vec3 },
+}
+
+impl Sdf {
+    pub fn sphere(radius: f32) -> Sdf {
+        Sdf::Sphere { radius }
+    }
+
+    pub fn cuboid(half_extents: Vec3) -> Sdf {
+        Sdf::Box { half_extents }
+    }
+
+    pub fn torus(major: f32, minor: f32) -> Sdf {
+        Sdf::Torus { major, minor }
+    }
+
+    pub fn translate(self, offset: Vec3) -> Sdf {
+        Sdf::Translate {
+            sdf: Box::new(self),
+            offset,
+        }
+    }
+
+    pub fn scale(self, factor: f32) -> Sdf {
+        Sdf::Scale {
+            sdf: Box::new(self),
+            factor,
+        }
+    }
+
+    pub fn round(self, radius: f32) -> Sdf {
+        Sdf::Round {
+            sdf: Box::new(self),
+            radius,
+        }
+    }
+
+    pub fn union(self, other: Sdf) -> Sdf {
+        Sdf::Union(Box::new(self), Box::new(other))
+    }
+
+    pub fn intersection(self, other: Sdf) -> Sdf {
+        Sdf::Intersection(Box::new(self), Box::new(other))
+    }
+
+    pub fn difference(self, other: Sdf) -> Sdf {
+        Sdf::Difference(Box::new(self), Box::new(other))
+    }
+
+    pub fn smooth_union(self, other: Sdf, k: f32) -> Sdf {
+        Sdf::SmoothUnion {
+            a: Box::new(self),
+            b: Box::new(other),
+            k,
+        }
+    }
+
+    pub fn twist(self, rate: f32) -> Sdf {
+        Sdf::Twist {
+            sdf: Box::new(self),
+            rate,
+        }
+    }
+
+    pub fn repeat(self, period: Vec3) -> Sdf {
+        Sdf::Repeat {
+            sdf: Box::new(self),
+            period,
+        }
+    }
+
+    pub fn distance(&self, p: Vec3) -> f32 {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::Box { half_extents } => {
+                let q = Vec3::new(
+                    p.x.abs() - half_extents.x,
+                    p.y.abs() - half_extents.y,
+                    p.z.abs() - half_extents.z,
+                );
+                let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0));
+                outside.length() + q.x.max(q.y.max(q.z)).min(0.0)
+            }
+            Sdf::Torus { major, minor } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major;
+                (ring * ring + p.y * p.y).sqrt() - minor
+            }
+            Sdf::Translate { sdf, offset } => sdf.distance(p - *offset),
+            Sdf::Scale { sdf, factor } => sdf.distance(p / *factor) * factor,
+            Sdf::Round { sdf, radius } => sdf.distance(p) - radius,
+            Sdf::Union(a, b) => a.distance(p).min(b.distance(p)),
+            Sdf::Intersection(a, b) => a.distance(p).max(b.distance(p)),
+            Sdf::Difference(a, b) => a.distance(p).max(-b.distance(p)),
+            Sdf::SmoothUnion { a, b, k } => {
+                let d1 = a.distance(p);
+                let d2 = b.distance(p);
+                let h = (0.5 + 0.5 * (d2 - d1) / k).clamp(0.0, 1.0);
+                d2 + (d1 - d2) * h - k * h * (1.0 - h)
+            }
+            Sdf::Twist { sdf, rate } => {
+                let (sin, cos) = (rate * p.y).sin_cos();
+                sdf.distance(Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z))
+            }
+            Sdf::Repeat { sdf, period } => {
+                let mut q = p;
+                for a in 0..3 {
+                    if period[a] > 0.0 {
+                        q[a] = p[a] - period[a] * (p[a] / period[a]).round();
+                    }
+                }
+                sdf.distance(q)
+            }
+        }
+    }
+
+    // central differences on a tetrahedron, four evaluations instead of six
+    pub fn gradient(&self, p: Vec3, h: f32) -> Vec3 {
+        let k0 = Vec3::new(1.0, -1.0, -1.0);
+        let k1 = Vec3::new(-1.0, -1.0, 1.0);
+        let k2 = Vec3::new(-1.0, 1.0, -1.0);
+        let k3 = Vec3::new(1.0, 1.0, 1.0);
+
+        (k0 * self.distance(p + k0 * h)
+            + k1 * self.distance(p + k1 * h)
+            + k2 * self.distance(p + k2 * h)
+            + k3 * self.distance(p + k3 * h))
+        .normalize()
+    }
+}
@@ -0,0 +1,103 @@
+use crate::color::Color;
+
+// visible range that dispersive paths pick their wavelength from, in nanometers
+pub const LAMBDA_MIN: f32 = 380.0;
+pub const LAMBDA_MAX: f32 = 720.0;
+
+// the sodium d line, where an index of refraction is quoted
+pub const LAMBDA_D: f32 = 587.6;
+
+// Fraunhofer F and C lines, the Abbe number compares the index at these
+const LAMBDA_F: f32 = 486.1;
+const LAMBDA_C: f32 = 656.3;
+
+// Per channel scale so a uniformly sampled wavelength averages out to white,
+// integrated from `wavelength_to_rgb` over [LAMBDA_MIN, LAMBDA_MAX].
+const WHITE_SCALE: [f32; 3] = [1.929_846, 2.946_889, 3.110_814];
+
+pub fn sample_wavelength(u: f32) -> f32 {
+    LAMBDA_MIN + u * (LAMBDA_MAX - LAMBDA_MIN)
+}
+
+// piecewise gaussian with a different width on either side of the peak
+fn lobe(lambda: f32, mu: f32, sigma_below: f32, sigma_above: f32) -> f32 {
+    let sigma = if lambda < mu {
+        sigma_below
+    } else {
+        sigma_above
+    };
+    let t = (lambda - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+// CIE 1931 color matching functions, the multi-lobe fit of Wyman et al. 2013
+fn cie_xyz(lambda: f32) -> (f32, f32, f32) {
+    let x = 1.056 * lobe(lambda, 599.8, 37.9, 31.0) + 0.362 * lobe(lambda, 442.0, 16.0, 26.7)
+        - 0.065 * lobe(lambda, 501.1, 20.4, 26.2);
+    let y = 0.821 * lobe(lambda, 568.8, 46.9, 40.5) + 0.286 * lobe(lambda, 530.9, 16.3, 31.1);
+    let z = 1.217 * lobe(lambda, 437.0, 11.8, 36.0) + 0.681 * lobe(lambda, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+// Linear RGB weight of a path carrying a single wavelength sampled with
+// `sample_wavelength`. Colors outside the gamut are clipped.
+pub fn wavelength_to_rgb(lambda: f32) -> Color {
+    let (x, y, z) = cie_xyz(lambda);
+    let r = 3.2406 * x - 1.5372 * y - 0.4986 * z;
+    let g = -0.9689 * x + 1.8758 * y + 0.0415 * z;
+    let b = 0.0557 * x - 0.2040 * y + 1.0570 * z;
+    Color::new(
+        r.max(0.0) * WHITE_SCALE[0],
+        g.max(0.0) * WHITE_SCALE[1],
+        b.max(0.0) * WHITE_SCALE[2],
+    )
+}
+
+// How the index of refraction of a dielectric changes with wavelength
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum Dispersion {
+    // n = a + b / lambda^2, lambda in micrometers
+    Cauchy { a: f32, b: f32 },
+    // n^2 = 1 + sum of b_i lambda^2 / (lambda^2 - c_i), lambda in micrometers
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    // Cauchy fit through an index at the d line and an Abbe number, lower Abbe
+    // numbers split light further apart
+    pub fn from_abbe(ir: f32, abbe: f32) -> Dispersion {
+        let inv2 = |lambda: f32| 1.0 / (lambda * 1e-3).powi(2);
+        let b = (ir - 1.0) / (abbe * (inv2(LAMBDA_F) - inv2(LAMBDA_C)));
+        Dispersion::Cauchy {
+            a: ir - b * inv2(LAMBDA_D),
+            b,
+        }
+    }
+
+    // Schott N-BK7 crown glass
+    pub fn bk7() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        }
+    }
+
+    pub fn diamond() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [4.3356, 0.3306, 0.0],
+            c: [0.011_236, 0.030_625, 0.0],
+        }
+    }
+
+    pub fn ior(&self, lambda: f32) -> f32 {
+        let l2 = (lambda * 1e-3).powi(2);
+        match *self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f32>();
+                n2.sqrt()
+            }
+        }
+    }
+}
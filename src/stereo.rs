@@ -0,0 +1,97 @@
+use image::imageops::replace;
+use image::{ImageBuffer, ImageResult, RgbImage};
+
+use crate::color::Color;
+use crate::projection::{OmniStereo, Projection};
+use crate::rendering::{Camera, RenderSettings};
+use crate::world::World;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    pub fn sign(&self) -> f32 {
+        match self {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum StereoLayout {
+    SideBySide,
+    OverUnder,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct StereoSettings {
+    pub interocular: f32,
+    // distance of the zero parallax plane
+    pub convergence: f32,
+    pub layout: StereoLayout,
+    // render each eye as an omni-directional stereo panorama instead of
+    // offsetting the camera's own projection
+    pub panoramic: bool,
+}
+
+impl Camera {
+    pub fn eye(&self, eye: Eye, stereo: &StereoSettings) -> Camera {
+        let mut camera = self.clone();
+        let half = eye.sign() * stereo.interocular / 2.0;
+
+        if stereo.panoramic {
+            camera.projection = OmniStereo::new(stereo.interocular, stereo.convergence, eye);
+            return camera;
+        }
+
+        camera.origin = self.origin + half * self.u;
+
+        // off-axis frustum: shift the viewport back towards the center line so both
+        // eyes agree on the image of points at the convergence distance
+        if let Projection::Perspective(_) = self.projection {
+            let focus_dist = self.focus_dist();
+            camera.llc = self.llc + half * (1.0 - focus_dist / stereo.convergence) * self.u;
+        }
+
+        camera
+    }
+
+    // `settings.width` is per eye, both eyes are packed into one image. Nothing is
+    // written per eye, `settings.output` is ignored
+    pub fn stereo_render(
+        &self,
+        world: &World,
+        background: &Color,
+        settings: &RenderSettings,
+        stereo: &StereoSettings,
+    ) -> ImageResult<ImageBuffer<image::Rgb<u8>, Vec<u8>>> {
+        let mut eye_settings = settings.clone();
+        eye_settings.output = None;
+
+        let left = self
+            .eye(Eye::Left, stereo)
+            .settings_render(world, background, &eye_settings)?;
+        let right =
+            self.eye(Eye::Right, stereo)
+                .settings_render(world, background, &eye_settings)?;
+
+        let (w, h) = (left.width(), left.height());
+        let mut img = match stereo.layout {
+            StereoLayout::SideBySide => RgbImage::new(w * 2, h),
+            StereoLayout::OverUnder => RgbImage::new(w, h * 2),
+        };
+
+        replace(&mut img, &left, 0, 0);
+        match stereo.layout {
+            StereoLayout::SideBySide => replace(&mut img, &right, w, 0),
+            StereoLayout::OverUnder => replace(&mut img, &right, 0, h),
+        }
+
+        Ok(img)
+    }
+}
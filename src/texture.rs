@@ -1,18 +1,20 @@
-// use std::sync::Arc;
+use std::fmt;
+use std::sync::Arc;
 
-use crate::{color::Color, vec3::Vec3};
-// use image::{DynamicImage, GenericImageView};
+use crate::{color::Color, perlin::Perlin, vec3::Vec3};
+use image::{DynamicImage, RgbImage};
 
 pub fn clamp(value: f32, lower: f32, upper: f32) -> f32 {
     value.min(upper).max(lower)
 }
 
 #[allow(dead_code)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub enum Texture {
     SolidColor(SolidColor),
     CheckerBoard(CheckerBoard),
-    // Image(Image),
+    Image(Image),
+    Noise(Noise),
 }
 
 impl Texture {
@@ -20,8 +22,8 @@ impl Texture {
         match *self {
             Texture::SolidColor(ref tex) => tex.get_color_uv(uv, point),
             Texture::CheckerBoard(ref tex) => tex.get_color_uv(uv, point),
-            //Texture::(ref obj) => obj.outward_normal(point, time),
-            // Texture::Image(ref tex) => tex.get_color_uv(uv, point),
+            Texture::Image(ref tex) => tex.get_color_uv(uv, point),
+            Texture::Noise(ref tex) => tex.get_color_uv(uv, point),
         }
     }
 }
@@ -79,38 +81,113 @@ impl UvMappable for CheckerBoard {
     }
 }
 
-// #[allow(dead_code)]
-// #[derive(Clone, Debug)]
-// pub struct Image {
-//     img: Arc<DynamicImage>,
-//     width: u32,
-//     height: u32,
-// }
+#[derive(Clone)]
+pub struct Image {
+    img: Arc<RgbImage>,
+    // false for data like normal and height maps that are stored linearly
+    srgb: bool,
+}
+
+impl Image {
+    // a color texture, decoded with the same gamma the renderer writes
+    pub fn new(img: &DynamicImage) -> Texture {
+        Texture::Image(Image {
+            img: Arc::new(img.to_rgb8()),
+            srgb: true,
+        })
+    }
 
-// impl Image {
-//     pub fn new(img: Arc<DynamicImage>) -> Texture {
-//         let width = img.width();
-//         let height = img.height();
+    // normal, height and other data maps, read as is
+    pub fn linear(img: &DynamicImage) -> Texture {
+        Texture::Image(Image {
+            img: Arc::new(img.to_rgb8()),
+            srgb: false,
+        })
+    }
 
-//         Texture::Image(Image { img, width, height })
-//     }
-// }
+    // opacity masks, from the alpha channel when the image has one and from the
+    // grey level otherwise
+    pub fn alpha(img: &DynamicImage) -> Texture {
+        if !img.color().has_alpha() {
+            return Image::linear(img);
+        }
+        let rgba = img.to_rgba8();
+        let alpha = RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+            let a = rgba.get_pixel(x, y)[3];
+            image::Rgb([a, a, a])
+        });
+        Texture::Image(Image {
+            img: Arc::new(alpha),
+            srgb: false,
+        })
+    }
+
+    fn texel(&self, i: i64, j: i64) -> Color {
+        let (width, height) = self.img.dimensions();
+        let pixel = self.img.get_pixel(
+            i.rem_euclid(width as i64) as u32,
+            j.rem_euclid(height as i64) as u32,
+        );
+        let color = Color::from_rgb(pixel[0], pixel[1], pixel[2]);
+        if self.srgb {
+            color * color
+        } else {
+            color
+        }
+    }
+}
 
-// impl UvMappable for Image {
-//     fn get_color_uv(&self, uv: (f32, f32), _point: Vec3) -> Color {
-//         let u = clamp(uv.0, 0.0, 1.0);
-//         let v = 1.0 - clamp(uv.1, 0.0, 1.0);
+// the pixels would otherwise end up in every material id and log line
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Image")
+            .field("dimensions", &self.img.dimensions())
+            .field("srgb", &self.srgb)
+            .finish()
+    }
+}
 
-//         let mut i = (u * self.width as f32) as u32;
-//         let mut j = (v * self.height as f32) as u32;
+impl UvMappable for Image {
+    // bilinear filtering, the image repeats outside of [0, 1]
+    fn get_color_uv(&self, uv: (f32, f32), _point: Vec3) -> Color {
+        let (width, height) = self.img.dimensions();
+        let x = uv.0 * width as f32 - 0.5;
+        let y = (1.0 - uv.1) * height as f32 - 0.5;
+        let (i, j) = (x.floor() as i64, y.floor() as i64);
+        let (fx, fy) = (x - x.floor(), y - y.floor());
+
+        let top = self.texel(i, j) * (1.0 - fx) + self.texel(i + 1, j) * fx;
+        let bottom = self.texel(i, j + 1) * (1.0 - fx) + self.texel(i + 1, j + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
 
-//         i = if i >= self.width { self.width - 1 } else { i };
-//         j = if j >= self.height { self.height - 1 } else { j };
+// grey Perlin noise in [0, 1], it only depends on the point so it works on
+// surfaces without uv
+#[derive(Clone)]
+pub struct Noise {
+    perlin: Arc<Perlin>,
+    scale: f32,
+}
 
-//         // const color_scale: f32 = 1.0 / 255.0;
+impl Noise {
+    pub fn new(scale: f32) -> Texture {
+        Texture::Noise(Noise {
+            perlin: Arc::new(Perlin::new()),
+            scale,
+        })
+    }
+}
 
-//         let pixel = self.img.get_pixel(j, i);
+impl fmt::Debug for Noise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Noise").field("scale", &self.scale).finish()
+    }
+}
 
-//         Color::from_rgb(pixel[0], pixel[1], pixel[2])
-//     }
-// }
+impl UvMappable for Noise {
+    fn get_color_uv(&self, _uv: (f32, f32), point: Vec3) -> Color {
+        let n = 0.5 * (1.0 + self.perlin.noise(self.scale * point));
+        Color::new(n, n, n)
+    }
+}
@@ -0,0 +1,188 @@
+use std::f32::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+
+use crate::color::Color;
+
+// wavelengths, in nanometers, that stand in for the channels of RGB paths
+const RGB_WAVELENGTHS: [f32; 3] = [630.0, 532.0, 465.0];
+
+#[derive(Copy, Clone, Debug)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Complex {
+        Complex { re, im }
+    }
+
+    fn real(re: f32) -> Complex {
+        Complex { re, im: 0.0 }
+    }
+
+    fn norm(self) -> f32 {
+        self.re * self.re + self.im * self.im
+    }
+
+    // principal root, so evanescent waves decay instead of growing
+    fn sqrt(self) -> Complex {
+        let r = self.norm().sqrt();
+        let re = (0.5 * (r + self.re)).max(0.0).sqrt();
+        let im = (0.5 * (r - self.re)).max(0.0).sqrt();
+        Complex::new(re, im.copysign(self.im))
+    }
+
+    fn exp(self) -> Complex {
+        let m = self.re.exp();
+        Complex::new(m * self.im.cos(), m * self.im.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, other: Complex) -> Complex {
+        let d = other.norm();
+        Complex::new(
+            (self.re * other.re + self.im * other.im) / d,
+            (self.im * other.re - self.re * other.im) / d,
+        )
+    }
+}
+
+// s and p amplitude reflection coefficients going from medium i into medium j
+fn fresnel_amplitudes(
+    ni: Complex,
+    cos_i: Complex,
+    nj: Complex,
+    cos_j: Complex,
+) -> (Complex, Complex) {
+    let rs = (ni * cos_i - nj * cos_j) / (ni * cos_i + nj * cos_j);
+    let rp = (nj * cos_i - ni * cos_j) / (nj * cos_i + ni * cos_j);
+    (rs, rp)
+}
+
+// A film of `thickness` nanometers with index of refraction `ior` on top of a
+// surface, like soap, oil or an anodized layer. Light bouncing inside it
+// interferes with light reflected off its top and tints the reflection.
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub struct ThinFilm {
+    pub thickness: f32,
+    pub ior: f32,
+}
+
+impl ThinFilm {
+    pub fn new(thickness: f32, ior: f32) -> ThinFilm {
+        ThinFilm { thickness, ior }
+    }
+
+    // Airy reflectance at one wavelength, summing every bounce inside the film.
+    // `outside` is the index light arrives from, `eta + ik` the one under the film.
+    fn airy(&self, cos_i: f32, outside: f32, eta: f32, k: f32, wavelength: f32) -> f32 {
+        let n1 = Complex::real(outside);
+        let n2 = Complex::real(self.ior);
+        let n3 = Complex::new(eta, k);
+
+        // Snell's law with complex angles covers total internal reflection too
+        let cos1 = Complex::real(cos_i.clamp(0.0, 1.0));
+        let sin1 = n1 * (Complex::real(1.0) - cos1 * cos1).sqrt();
+        let cos_in = |n: Complex| {
+            let s = sin1 / n;
+            (Complex::real(1.0) - s * s).sqrt()
+        };
+        let (cos2, cos3) = (cos_in(n2), cos_in(n3));
+
+        let (rs12, rp12) = fresnel_amplitudes(n1, cos1, n2, cos2);
+        let (rs23, rp23) = fresnel_amplitudes(n2, cos2, n3, cos3);
+
+        // phase picked up by a round trip through the film
+        let delta = Complex::real(4.0 * PI * self.thickness / wavelength) * n2 * cos2;
+        let phase = (Complex::new(0.0, 1.0) * delta).exp();
+
+        let airy = |r12: Complex, r23: Complex| {
+            let r = (r12 + r23 * phase) / (Complex::real(1.0) + r12 * r23 * phase);
+            r.norm().min(1.0)
+        };
+        0.5 * (airy(rs12, rs23) + airy(rp12, rp23))
+    }
+
+    // Reflectance of the film on a base of index `eta + ik`. RGB paths get one
+    // representative wavelength per channel, paths split by dispersion get the
+    // reflectance at their own wavelength.
+    pub fn reflectance(
+        &self,
+        cos_i: f32,
+        outside: f32,
+        eta: Color,
+        k: Color,
+        wavelength: f32,
+    ) -> Color {
+        let channel = |c: usize| {
+            let pick = |color: Color| [color.r, color.g, color.b][c];
+            (pick(eta), pick(k))
+        };
+
+        if wavelength == 0.0 {
+            let r = |c: usize| {
+                let (eta, k) = channel(c);
+                self.airy(cos_i, outside, eta, k, RGB_WAVELENGTHS[c])
+            };
+            Color::new(r(0), r(1), r(2))
+        } else {
+            // the base is only known at the channel wavelengths, use the closest
+            let c = (0..3)
+                .min_by(|&a, &b| {
+                    let d = |i: usize| (RGB_WAVELENGTHS[i] - wavelength).abs();
+                    d(a).total_cmp(&d(b))
+                })
+                .unwrap();
+            let (eta, k) = channel(c);
+            let r = self.airy(cos_i, outside, eta, k, wavelength);
+            Color::new(r, r, r)
+        }
+    }
+}
+
+// Complex index of refraction of a metal with normal incidence reflectance
+// `albedo` and the same edge tint (Gulbrandsen 2014), so a film of zero
+// thickness reflects just like the bare metal.
+pub fn conductor_from_albedo(albedo: Color) -> (Color, Color) {
+    let fit = |r: f32| {
+        let r = r.clamp(0.0, 0.99);
+        let sr = r.sqrt();
+        let n = r * (1.0 - r) / (1.0 + r) + (1.0 - r) * (1.0 + sr) / (1.0 - sr);
+        let k2 = (r * (n + 1.0).powi(2) - (n - 1.0).powi(2)) / (1.0 - r);
+        (n, k2.max(0.0).sqrt())
+    };
+    let (r, g, b) = (fit(albedo.r), fit(albedo.g), fit(albedo.b));
+    (Color::new(r.0, g.0, b.0), Color::new(r.1, g.1, b.1))
+}
@@ -0,0 +1,306 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use rayon::prelude::*;
+
+use crate::adaptive::PixelStats;
+use crate::color::Color;
+use crate::film::Film;
+use crate::progressive::interrupted;
+use crate::rendering::{Camera, RenderSettings};
+use crate::sampler::Sampler;
+use crate::world::World;
+
+// a rectangle of the image in pixels, (x, y) is its top left corner
+#[derive(Copy, Clone, Debug)]
+pub struct Tile {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[allow(dead_code)]
+#[derive(Copy, Clone, Debug)]
+pub enum TileOrder {
+    // left to right, top to bottom
+    Scanline,
+    // outwards from the center of the image, so the subject shows up first
+    Spiral,
+    // along a Hilbert curve, neighbouring tiles are rendered close together in time
+    Hilbert,
+}
+
+pub struct TileProgress {
+    pub tile: Tile,
+    pub done: usize,
+    pub total: usize,
+}
+
+// distance along a Hilbert curve filling an n by n grid, n a power of two
+fn hilbert_index(n: u32, mut x: u32, mut y: u32) -> u32 {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        d += s * s * ((3 * rx) ^ ry);
+
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+pub fn tiles(width: u32, height: u32, tile_size: u32, order: TileOrder) -> Vec<Tile> {
+    let tile_size = tile_size.max(1);
+    let cols = width.div_ceil(tile_size);
+    let rows = height.div_ceil(tile_size);
+
+    let mut cells: Vec<(u32, u32)> = (0..rows)
+        .flat_map(|r| (0..cols).map(move |c| (c, r)))
+        .collect();
+
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let (cx, cy) = ((cols as f32 - 1.0) / 2.0, (rows as f32 - 1.0) / 2.0);
+            let key = |&(c, r): &(u32, u32)| {
+                let (dx, dy) = (c as f32 - cx, r as f32 - cy);
+                // square rings around the center, walked around by angle
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let n = cols.max(rows).next_power_of_two();
+            cells.sort_by_key(|&(c, r)| hilbert_index(n, c, r));
+        }
+    }
+
+    cells
+        .into_iter()
+        .map(|(c, r)| {
+            let (x, y) = (c * tile_size, r * tile_size);
+            Tile {
+                x,
+                y,
+                width: tile_size.min(width - x),
+                height: tile_size.min(height - y),
+            }
+        })
+        .collect()
+}
+
+// callback for `tile_render` that saves the film every `every` finished tiles
+pub fn preview_writer(path: &str, every: usize) -> impl Fn(&TileProgress, &Film) + Sync {
+    let path = path.to_string();
+    let every = every.max(1);
+    move |progress, film| {
+        if progress.done % every == 0 || progress.done == progress.total {
+            film.to_image().save(&path).unwrap();
+        }
+    }
+}
+
+impl Camera {
+    pub fn render_tile(
+        &self,
+        world: &World,
+        background: &Color,
+        tile: &Tile,
+        settings: &RenderSettings,
+    ) -> Film {
+        let width = settings.width;
+        let height = settings.height(self);
+        let margin = settings.filter_margin();
+        let mut film = Film::for_tile(tile, margin, &settings.aovs);
+        let mut sampler = settings.create_sampler();
+        let aovs = !settings.aovs.is_empty();
+
+        for y in 0..tile.height {
+            for x in 0..tile.width {
+                let i = tile.x + x;
+                let j = height - 1 - (tile.y + y);
+
+                // splats one sample and returns its color
+                let mut sample = |film: &mut Film, index: u32| {
+                    sampler.start_pixel_sample(i, j, settings.first_sample + index);
+                    let (du, dv) = sampler.next_2d();
+                    let u = ((i as f32) + du) / (width as f32);
+                    let v = ((j as f32) + dv) / (height as f32);
+                    let ray = self.get_ray(u, v, &mut sampler);
+                    let (px, py) = (i as f32 + du, (height - j) as f32 - dv);
+
+                    let color = if aovs {
+                        let aov_sample = self.trace_aovs(
+                            ray,
+                            world,
+                            background,
+                            settings.max_depth,
+                            &mut sampler,
+                        );
+                        film.splat_aovs(&settings.filter, px, py, &aov_sample);
+                        aov_sample.beauty()
+                    } else {
+                        ray.bvh2_color(world, background, settings.max_depth, &mut sampler)
+                    };
+                    film.splat(&settings.filter, px, py, color);
+                    color
+                };
+
+                let taken = match settings.adaptive {
+                    None => {
+                        for s in 0..settings.samples_per_pixel {
+                            sample(&mut film, s);
+                        }
+                        settings.samples_per_pixel
+                    }
+                    Some(adaptive) => {
+                        let mut stats = PixelStats::new();
+                        while !stats.converged(&adaptive) {
+                            let color = sample(&mut film, stats.n);
+                            stats.add(color);
+                        }
+                        stats.n
+                    }
+                };
+                film.count_samples(x + margin, y + margin, taken);
+            }
+        }
+
+        film
+    }
+
+    // Every rayon worker pulls the next tile in `settings.tile_order` and renders it
+    // on its own. `on_tile` runs after each tile is merged, while the film is locked.
+    pub fn tile_render(
+        &self,
+        world: &World,
+        background: &Color,
+        settings: &RenderSettings,
+        on_tile: &(dyn Fn(&TileProgress, &Film) + Sync),
+    ) -> Film {
+        let height = settings.height(self);
+        let tiles = tiles(
+            settings.width,
+            height,
+            settings.tile_size,
+            settings.tile_order,
+        );
+
+        let film = Mutex::new(Film::with_aovs(settings.width, height, &settings.aovs));
+        let tile_films: Mutex<Vec<Option<Film>>> = Mutex::new(vec![None; tiles.len()]);
+        let next = AtomicUsize::new(0);
+        let done = AtomicUsize::new(0);
+
+        (0..rayon::current_num_threads())
+            .into_par_iter()
+            .for_each(|_| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                // a stopped progressive render keeps the tiles it has so far
+                if i >= tiles.len() || interrupted() {
+                    break;
+                }
+
+                let tile = tiles[i];
+                let tile_film = self.render_tile(world, background, &tile, settings);
+
+                let mut film = film.lock().unwrap();
+                film.merge(&tile_film);
+                tile_films.lock().unwrap()[i] = Some(tile_film);
+                let progress = TileProgress {
+                    tile,
+                    done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                    total: tiles.len(),
+                };
+                on_tile(&progress, &film);
+            });
+
+        // Tiles overlap where the filter reaches past their edges, and the order they
+        // finished in depends on the threads. Merging again in tile order keeps the
+        // result bit for bit the same on any number of threads.
+        let mut film = Film::with_aovs(settings.width, height, &settings.aovs);
+        for tile_film in tile_films.into_inner().unwrap().iter().flatten() {
+            film.merge(tile_film);
+        }
+        film
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::{EmissiveDiffuse, Lambertian};
+    use crate::objects::{Quad, Sphere};
+    use crate::sampler::SamplerType;
+    use crate::texture::SolidColor;
+    use crate::vec3::Vec3;
+
+    fn scene() -> (Camera, World) {
+        let grey = Lambertian::new(SolidColor::new(Color::new(0.5, 0.5, 0.5)));
+        let light = Quad::new(
+            Vec3::new(-1.0, 3.0, -1.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, 2.0),
+            EmissiveDiffuse::new(SolidColor::new(Color::new(4.0, 4.0, 4.0))),
+        );
+        let objects = vec![
+            Sphere::new(Vec3::new(0.0, -100.5, 0.0), 100.0, grey.clone()),
+            Sphere::new(Vec3::new(0.0, 0.0, 0.0), 0.5, grey),
+            light.clone(),
+        ];
+        let world = World::new(objects, 0.0, 1.0).with_lights(vec![light]);
+        let camera = Camera::new(
+            Vec3::new(0.0, 1.0, 3.0),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.5,
+            0.1,
+            3.0,
+            0.0,
+            1.0,
+        );
+        (camera, world)
+    }
+
+    fn render(threads: usize, order: TileOrder, seed: u64) -> Vec<u32> {
+        let (camera, world) = scene();
+        let mut settings = RenderSettings::new(24, 4, 4);
+        settings.seed = seed;
+        settings.sampler = SamplerType::Sobol;
+        settings.tile_size = 8;
+        settings.tile_order = order;
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        let film = pool.install(|| {
+            camera.tile_render(&world, &Color::new(0.1, 0.1, 0.1), &settings, &|_, _| {})
+        });
+
+        (0..film.height)
+            .flat_map(|y| (0..film.width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let c = film.color(x, y);
+                vec![c.r.to_bits(), c.g.to_bits(), c.b.to_bits()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_renders_the_same_image() {
+        let reference = render(1, TileOrder::Scanline, 7);
+        assert_eq!(reference, render(4, TileOrder::Scanline, 7));
+        assert_eq!(reference, render(3, TileOrder::Hilbert, 7));
+        assert_ne!(reference, render(1, TileOrder::Scanline, 8));
+    }
+}
@@ -0,0 +1,196 @@
+use crate::aabb::Aabb;
+use crate::vec3::Vec3;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Mat3 {
+    pub rows: [Vec3; 3],
+}
+
+impl Mat3 {
+    pub fn identity() -> Mat3 {
+        Mat3::diagonal(Vec3::from_one(1.0))
+    }
+
+    pub fn diagonal(d: Vec3) -> Mat3 {
+        Mat3 {
+            rows: [
+                Vec3::new(d.x, 0.0, 0.0),
+                Vec3::new(0.0, d.y, 0.0),
+                Vec3::new(0.0, 0.0, d.z),
+            ],
+        }
+    }
+
+    // rotation about x, then y, then z. angles in degrees
+    pub fn from_euler(degrees: Vec3) -> Mat3 {
+        let (sx, cx) = degrees.x.to_radians().sin_cos();
+        let (sy, cy) = degrees.y.to_radians().sin_cos();
+        let (sz, cz) = degrees.z.to_radians().sin_cos();
+
+        let rx = Mat3 {
+            rows: [
+                Vec3::new(1.0, 0.0, 0.0),
+                Vec3::new(0.0, cx, -sx),
+                Vec3::new(0.0, sx, cx),
+            ],
+        };
+        let ry = Mat3 {
+            rows: [
+                Vec3::new(cy, 0.0, sy),
+                Vec3::new(0.0, 1.0, 0.0),
+                Vec3::new(-sy, 0.0, cy),
+            ],
+        };
+        let rz = Mat3 {
+            rows: [
+                Vec3::new(cz, -sz, 0.0),
+                Vec3::new(sz, cz, 0.0),
+                Vec3::new(0.0, 0.0, 1.0),
+            ],
+        };
+
+        rz.mul(&ry.mul(&rx))
+    }
+
+    pub fn transpose(&self) -> Mat3 {
+        let r = &self.rows;
+        Mat3 {
+            rows: [
+                Vec3::new(r[0].x, r[1].x, r[2].x),
+                Vec3::new(r[0].y, r[1].y, r[2].y),
+                Vec3::new(r[0].z, r[1].z, r[2].z),
+            ],
+        }
+    }
+
+    pub fn mul(&self, other: &Mat3) -> Mat3 {
+        let columns = other.transpose();
+        let mut rows = [Vec3::zero(); 3];
+        for (i, row) in rows.iter_mut().enumerate() {
+            *row = Vec3::new(
+                self.rows[i].dot(&columns.rows[0]),
+                self.rows[i].dot(&columns.rows[1]),
+                self.rows[i].dot(&columns.rows[2]),
+            );
+        }
+        Mat3 { rows }
+    }
+
+    pub fn mul_vec(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            self.rows[0].dot(v),
+            self.rows[1].dot(v),
+            self.rows[2].dot(v),
+        )
+    }
+}
+
+// placement of an object at one moment: scale, then rotate, then translate
+#[derive(Copy, Clone, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub translation: Vec3,
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, translation: Vec3, rotation: Vec3, scale: Vec3) -> Keyframe {
+        Keyframe {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn lerp(&self, other: &Keyframe, t: f32) -> Keyframe {
+        Keyframe {
+            time: self.time + (other.time - self.time) * t,
+            translation: self.translation + (other.translation - self.translation) * t,
+            rotation: self.rotation + (other.rotation - self.rotation) * t,
+            scale: self.scale + (other.scale - self.scale) * t,
+        }
+    }
+
+    pub fn to_transform(&self) -> Transform {
+        Transform::new(self.translation, self.rotation, self.scale)
+    }
+}
+
+// keyframes must be sorted by time, times outside the range hold the end keys
+pub fn interpolate(keyframes: &[Keyframe], time: f32) -> Keyframe {
+    let first = keyframes[0];
+    let last = keyframes[keyframes.len() - 1];
+    if time <= first.time {
+        return first;
+    }
+    if time >= last.time {
+        return last;
+    }
+
+    let next = keyframes.iter().position(|k| k.time > time).unwrap();
+    let (a, b) = (keyframes[next - 1], keyframes[next]);
+    a.lerp(&b, (time - a.time) / (b.time - a.time))
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Transform {
+    pub translation: Vec3,
+    linear: Mat3,
+    inverse: Mat3,
+    normal: Mat3,
+}
+
+impl Transform {
+    pub fn new(translation: Vec3, rotation: Vec3, scale: Vec3) -> Transform {
+        let r = Mat3::from_euler(rotation);
+        let inv_scale = Mat3::diagonal(Vec3::new(1.0 / scale.x, 1.0 / scale.y, 1.0 / scale.z));
+
+        Transform {
+            translation,
+            linear: r.mul(&Mat3::diagonal(scale)),
+            inverse: inv_scale.mul(&r.transpose()),
+            // inverse transpose of the linear part
+            normal: r.mul(&inv_scale),
+        }
+    }
+
+    pub fn point(&self, p: &Vec3) -> Vec3 {
+        self.linear.mul_vec(p) + self.translation
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        self.linear.mul_vec(v)
+    }
+
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.normal.mul_vec(n).normalize()
+    }
+
+    pub fn inverse_point(&self, p: &Vec3) -> Vec3 {
+        self.inverse.mul_vec(&(*p - self.translation))
+    }
+
+    pub fn inverse_vector(&self, v: &Vec3) -> Vec3 {
+        self.inverse.mul_vec(v)
+    }
+
+    pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+        let mut min = Vec3::from_one(f32::MAX);
+        let mut max = Vec3::from_one(f32::MIN);
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 { bbox.min.x } else { bbox.max.x },
+                if i & 2 == 0 { bbox.min.y } else { bbox.max.y },
+                if i & 4 == 0 { bbox.min.z } else { bbox.max.z },
+            );
+            let p = self.point(&corner);
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+        Aabb { min, max }
+    }
+}
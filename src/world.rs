@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use crate::bvh2::BVH;
+use crate::intersection::Intersection;
+use crate::objects::Object;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::Vec3;
+
+// Objects without a bounding box (infinite planes) can not live in the BVH,
+// so they are kept on the side and tested against every ray.
+#[derive(Clone, Debug)]
+pub struct World {
+    bvh: Option<BVH>,
+    unbounded: Vec<(u32, Object)>,
+    // one per object, used to name its Cryptomatte matte
+    pub names: Vec<String>,
+    // one per object, objects with the same material name share a material id
+    pub materials: Vec<String>,
+    material_ids: Vec<u32>,
+    // shapes rays are sent towards to pick up light directly, see `Ray::bvh2_color`
+    pub lights: Vec<Object>,
+}
+
+// every object gets the index of the first material with its name, plus one
+fn material_ids(materials: &[String]) -> Vec<u32> {
+    let mut first = HashMap::new();
+    materials
+        .iter()
+        .enumerate()
+        .map(|(i, name)| *first.entry(name).or_insert(i as u32 + 1))
+        .collect()
+}
+
+impl World {
+    // objects are identified by their index in `objects`, see `Intersection::object_id`
+    pub fn new(objects: Vec<Object>, time0: f32, time1: f32) -> World {
+        let names = (0..objects.len()).map(|i| format!("object{}", i)).collect();
+        World::named(objects, names, time0, time1)
+    }
+
+    pub fn named(objects: Vec<Object>, names: Vec<String>, time0: f32, time1: f32) -> World {
+        assert_eq!(objects.len(), names.len());
+        let materials: Vec<String> = (0..objects.len())
+            .map(|i| format!("material{}", i))
+            .collect();
+        let material_ids = material_ids(&materials);
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .enumerate()
+            .map(|(id, o)| (id as u32, o))
+            .partition(|(_, o)| o.bounding_box(time0, time1).is_some());
+
+        let bvh = if bounded.is_empty() {
+            None
+        } else {
+            Some(BVH::with_ids(
+                bounded
+                    .into_iter()
+                    .map(|(id, o)| (id, Box::new(o)))
+                    .collect(),
+                time0,
+                time1,
+            ))
+        };
+
+        World {
+            bvh,
+            unbounded,
+            names,
+            materials,
+            material_ids,
+            lights: Vec::new(),
+        }
+    }
+
+    // Names the material of every object, materials have no identity of their own
+    // so without names every object counts as having a different one
+    pub fn with_materials(mut self, materials: Vec<String>) -> World {
+        assert_eq!(materials.len(), self.names.len());
+        self.material_ids = material_ids(&materials);
+        self.materials = materials;
+        self
+    }
+
+    // Emissive quads and planes, or copies of their shape, to sample light from.
+    // They are only used to pick directions, the light still has to be in the world.
+    pub fn with_lights(mut self, lights: Vec<Object>) -> World {
+        self.lights = lights;
+        self
+    }
+
+    // direction from `origin` towards a point on one of the lights, not normalized
+    pub fn sample_light(&self, origin: &Vec3, sampler: &mut dyn Sampler) -> Vec3 {
+        let n = self.lights.len();
+        let k = ((sampler.next_1d() * n as f32) as usize).min(n - 1);
+        self.lights[k].random(origin, sampler)
+    }
+
+    // solid angle density of `sample_light` picking `direction`
+    pub fn light_pdf(&self, origin: &Vec3, direction: &Vec3) -> f32 {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let sum: f32 = self
+            .lights
+            .iter()
+            .map(|l| l.pdf_value(origin, direction))
+            .sum();
+        sum / self.lights.len() as f32
+    }
+
+    pub fn material_id(&self, object_id: u32) -> u32 {
+        self.material_ids[object_id as usize]
+    }
+
+    pub fn intersects(
+        &self,
+        ray: &Ray,
+        t_min: f32,
+        mut t_max: f32,
+        sampler: &mut dyn Sampler,
+    ) -> Option<Intersection<'_>> {
+        let mut closest = match &self.bvh {
+            Some(bvh) => bvh.intersects(ray, t_min, t_max, sampler),
+            None => None,
+        };
+
+        if let Some(hit) = &closest {
+            t_max = hit.distance;
+        }
+
+        for (id, object) in self.unbounded.iter() {
+            if let Some(mut hit) = object.intersects_opaque(ray, t_min, t_max, sampler) {
+                t_max = hit.distance;
+                hit.object_id = *id;
+                closest = Some(hit);
+            }
+        }
+
+        closest
+    }
+}